@@ -0,0 +1,427 @@
+//! Applying and undoing single patches on disk.
+
+use crate::atomic::atomic_write;
+use crate::journal::{content_hash, journal_applied, journal_undone};
+use crate::matching::{MatchInfo, MatchSpan, MatchTier, find_matches};
+use crate::patch::{PatchEntry, PatchOperation, PatchStatus};
+use crate::sandbox::PathSandbox;
+use crate::text::{TextFormat, normalized_body, splice_raw};
+use std::{fs, path::Path};
+
+/// Applies a patch to the file it names and records the result in `patch`.
+/// Successful patches keep a backup for undo and are written to the journal.
+pub fn apply_patch(patch: &mut PatchEntry, sandbox: &PathSandbox) {
+    apply_patch_with(patch, sandbox, true);
+}
+
+/// [`apply_patch`], or, without `allow_fuzzy`, the variant for patches nobody has looked at
+/// yet (auto-apply): a SEARCH block only the fuzzy tier found is not written but left
+/// `Pending`, with its match info and a warning, until the user applies it.
+pub(crate) fn apply_patch_with(patch: &mut PatchEntry, sandbox: &PathSandbox, allow_fuzzy: bool) {
+    let path = match sandbox.resolve(&patch.data.file_path) {
+        Ok(p) => p,
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("Path rejected: {}", e));
+            return;
+        }
+    };
+    match patch.data.operation {
+        PatchOperation::Replace => apply_replace(patch, &path, &sandbox.root, allow_fuzzy),
+        PatchOperation::Create => apply_create(patch, &path, &sandbox.root),
+        PatchOperation::Delete => apply_delete(patch, &path, &sandbox.root),
+        PatchOperation::Rename => {
+            let target = patch.data.new_path.clone().unwrap_or_default();
+            match sandbox.resolve(&target) {
+                Ok(new_path) => apply_rename(patch, &path, &new_path, &sandbox.root),
+                Err(e) => patch.status = PatchStatus::Failed(format!("Path rejected: {}", e)),
+            }
+        }
+    }
+}
+
+fn apply_create(patch: &mut PatchEntry, path: &Path, root: &Path) {
+    let content = patch.data.replace_content.clone();
+    // Folders that do not exist yet, deepest first, so a failed create can take them back.
+    let new_dirs: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .collect();
+    if let Err(e) = path.parent().map_or(Ok(()), fs::create_dir_all) {
+        remove_new_dirs(&new_dirs);
+        patch.status = PatchStatus::Failed(format!("IO Error: creating folders: {}", e));
+        return;
+    }
+    // Claim the name first so a file that appears in the meantime is never overwritten.
+    if let Err(e) = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        remove_new_dirs(&new_dirs);
+        patch.status = PatchStatus::Failed(if e.kind() == std::io::ErrorKind::AlreadyExists {
+            "File already exists (use a SEARCH/REPLACE block to edit it)".to_string()
+        } else {
+            format!("IO Error: creating '{}': {}", path.display(), e)
+        });
+        return;
+    }
+    if let Err(e) = atomic_write(path, &content) {
+        let _ = fs::remove_file(path);
+        remove_new_dirs(&new_dirs);
+        patch.status = PatchStatus::Failed(format!("IO Error: {}", e));
+        return;
+    }
+    patch.status = PatchStatus::Success;
+    // An empty backup stands for "the file did not exist".
+    patch.backup_content = Some(String::new());
+    patch.post_hash = Some(content_hash(&content));
+    journal_applied(root, patch, "", &content);
+}
+
+/// Removes folders a failed create made, deepest first. Only empty folders go.
+fn remove_new_dirs(dirs: &[&Path]) {
+    for dir in dirs {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+fn apply_delete(patch: &mut PatchEntry, path: &Path, root: &Path) {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("File missing: {}", e));
+            return;
+        }
+    };
+    if let Err(e) = fs::remove_file(path) {
+        patch.status = PatchStatus::Failed(format!("IO Error: {}", e));
+        return;
+    }
+    patch.status = PatchStatus::Success;
+    patch.post_hash = Some(content_hash(""));
+    journal_applied(root, patch, &content, "");
+    patch.backup_content = Some(content);
+}
+
+fn apply_rename(patch: &mut PatchEntry, from: &Path, to: &Path, root: &Path) {
+    let content = match fs::read_to_string(from) {
+        Ok(c) => c,
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("File missing: {}", e));
+            return;
+        }
+    };
+    if to.exists() {
+        patch.status = PatchStatus::Failed(format!(
+            "Rename target '{}' already exists",
+            patch.data.new_path.as_deref().unwrap_or_default()
+        ));
+        return;
+    }
+    let result = match to.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+    .and_then(|_| fs::rename(from, to));
+    if let Err(e) = result {
+        patch.status = PatchStatus::Failed(format!("IO Error: {}", e));
+        return;
+    }
+    patch.status = PatchStatus::Success;
+    patch.post_hash = Some(content_hash(&content));
+    journal_applied(root, patch, &content, &content);
+    patch.backup_content = Some(content);
+}
+
+fn apply_replace(patch: &mut PatchEntry, path: &Path, root: &Path, allow_fuzzy: bool) {
+    match fs::read_to_string(path) {
+        Ok(raw_content) => {
+            let content = normalized_body(&raw_content);
+            let search_norm = patch.data.search_content.replace("\r\n", "\n");
+            let replace_norm = patch.data.replace_content.replace("\r\n", "\n");
+
+            let found = match patch.data.line_hint {
+                // A pure insertion hunk from a unified diff: add after the hinted line, or at
+                // the very start for line 0.
+                Some(hint) if search_norm.trim().is_empty() => {
+                    let start = match hint {
+                        0 => 0,
+                        _ => content
+                            .match_indices('\n')
+                            .nth(hint - 1)
+                            .map_or(content.len(), |(i, _)| i + 1),
+                    };
+                    // Past a last line without a newline, the newline goes in front instead.
+                    let replacement = if start == content.len()
+                        && !content.is_empty()
+                        && !content.ends_with('\n')
+                    {
+                        format!("\n{}", replace_norm)
+                    } else {
+                        format!("{}\n", replace_norm)
+                    };
+                    let info = MatchInfo {
+                        tier: MatchTier::Exact,
+                        score: 1.0,
+                    };
+                    let span = MatchSpan {
+                        start,
+                        end: start,
+                        line: hint + 1,
+                        replacement,
+                    };
+                    Some((info, vec![span]))
+                }
+                _ => find_matches(&content, &search_norm, &replace_norm),
+            };
+            let Some((match_info, spans)) = found else {
+                patch.match_info = None;
+                patch.status = PatchStatus::Failed(
+                    "Search text not found (Check tabs/whitespace)".to_string(),
+                );
+                return;
+            };
+
+            let span = if spans.len() == 1 {
+                &spans[0]
+            } else if let Some(span) = spans.iter().find(|s| Some(s.line) == patch.target_line) {
+                span
+            } else if let Some(hint) = patch.data.line_hint {
+                // Like `patch`, take the occurrence closest to where the hunk header says.
+                spans.iter().min_by_key(|s| s.line.abs_diff(hint)).unwrap()
+            } else {
+                patch.match_info = Some(match_info);
+                patch.status = PatchStatus::Ambiguous(spans.iter().map(|s| s.line).collect());
+                return;
+            };
+
+            if match_info.tier == MatchTier::Fuzzy && !allow_fuzzy {
+                patch.warning = Some(format!(
+                    "Only a fuzzy match ({:.0}%) at line {}; review it and apply it by hand.",
+                    match_info.score * 100.0,
+                    span.line
+                ));
+                patch.match_info = Some(match_info);
+                patch.status = PatchStatus::Pending;
+                return;
+            }
+
+            let new_content = splice_raw(&raw_content, span);
+            patch.match_info = Some(match_info);
+            let offset_note = patch
+                .data
+                .line_hint
+                .filter(|hint| *hint != span.line && !search_norm.trim().is_empty())
+                .map(|hint| {
+                    format!(
+                        "Hunk applied at line {} (offset {} lines).",
+                        span.line,
+                        span.line as i64 - hint as i64
+                    )
+                });
+            let notes: Vec<String> = offset_note
+                .into_iter()
+                .chain(TextFormat::detect(&raw_content).mixed_warning())
+                .collect();
+            patch.warning = (!notes.is_empty()).then(|| notes.join(" "));
+            if let Err(e) = atomic_write(path, &new_content) {
+                patch.status = PatchStatus::Failed(format!("IO Error: {}", e));
+            } else {
+                patch.status = PatchStatus::Success;
+                patch.post_hash = Some(content_hash(&new_content));
+                journal_applied(root, patch, &raw_content, &new_content);
+                patch.backup_content = Some(raw_content);
+            }
+        }
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("File missing: {}", e));
+        }
+    }
+}
+
+/// Undoes a patch without clobbering later edits to the same file.
+/// Each operation first checks that the file still looks the way the patch left it. When it
+/// does not and the change cannot be reversed in place, the current content is kept in
+/// `undo_conflict` until the user confirms a full restore.
+pub fn undo_patch(patch: &mut PatchEntry, sandbox: &PathSandbox) {
+    if patch.backup_content.is_none() {
+        return;
+    }
+    let path = match sandbox.resolve(&patch.data.file_path) {
+        Ok(p) => p,
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("Path rejected: {}", e));
+            return;
+        }
+    };
+    match patch.data.operation {
+        PatchOperation::Replace => undo_replace(patch, &path, &sandbox.root),
+        PatchOperation::Create => undo_create(patch, &path, &sandbox.root),
+        PatchOperation::Delete => undo_delete(patch, &path, &sandbox.root),
+        PatchOperation::Rename => {
+            let target = patch.data.new_path.clone().unwrap_or_default();
+            match sandbox.resolve(&target) {
+                Ok(new_path) => undo_rename(patch, &path, &new_path, &sandbox.root),
+                Err(e) => patch.status = PatchStatus::Failed(format!("Path rejected: {}", e)),
+            }
+        }
+    }
+}
+
+/// Removes a created file, unless it was edited since, in which case the user has to confirm.
+fn undo_create(patch: &mut PatchEntry, path: &Path, root: &Path) {
+    let current = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => {
+            // Already gone, nothing to lose.
+            mark_undone(patch, None, root);
+            return;
+        }
+    };
+    if patch.post_hash.as_deref() != Some(content_hash(&current).as_str()) {
+        patch.undo_conflict = Some(current);
+        return;
+    }
+    if let Err(e) = fs::remove_file(path) {
+        patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+    } else {
+        mark_undone(patch, None, root);
+    }
+}
+
+/// Recreates a deleted file, unless something new was written to the same path since.
+fn undo_delete(patch: &mut PatchEntry, path: &Path, root: &Path) {
+    if let Ok(current) = fs::read_to_string(path) {
+        patch.undo_conflict = Some(current);
+        return;
+    }
+    let backup = patch.backup_content.clone().unwrap_or_default();
+    let result = match path.parent() {
+        Some(parent) => fs::create_dir_all(parent).map_err(|e| format!("creating folders: {}", e)),
+        None => Ok(()),
+    }
+    .and_then(|_| atomic_write(path, &backup));
+    if let Err(e) = result {
+        patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+    } else {
+        mark_undone(patch, None, root);
+    }
+}
+
+/// Moves a renamed file back. Edits made after the rename travel with the file.
+fn undo_rename(patch: &mut PatchEntry, from: &Path, to: &Path, root: &Path) {
+    if !to.exists() {
+        patch.status = PatchStatus::Failed(format!(
+            "Undo failed: '{}' no longer exists",
+            patch.data.new_path.as_deref().unwrap_or_default()
+        ));
+    } else if from.exists() {
+        patch.status = PatchStatus::Failed(format!(
+            "Undo failed: '{}' exists again",
+            patch.data.file_path
+        ));
+    } else if let Err(e) = fs::rename(to, from) {
+        patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+    } else {
+        mark_undone(patch, None, root);
+    }
+}
+
+/// Restores the backup if the file is unchanged since the patch, otherwise reverses the patch
+/// in place (REPLACEWITH back to SEARCH). The reverse patch must find the replaced text as it
+/// was written, up to trailing whitespace; a looser match means it was edited since, which
+/// is a conflict.
+fn undo_replace(patch: &mut PatchEntry, path: &Path, root: &Path) {
+    let backup = patch.backup_content.clone().unwrap_or_default();
+    let current = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+            return;
+        }
+    };
+
+    if patch.post_hash.as_deref() == Some(content_hash(&current).as_str()) {
+        if let Err(e) = atomic_write(path, &backup) {
+            patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+        } else {
+            mark_undone(patch, None, root);
+        }
+        return;
+    }
+
+    let content = normalized_body(&current);
+    let search_norm = patch.data.replace_content.replace("\r\n", "\n");
+    let replace_norm = patch.data.search_content.replace("\r\n", "\n");
+    match find_matches(&content, &search_norm, &replace_norm) {
+        Some((info, spans))
+            if spans.len() == 1
+                && matches!(info.tier, MatchTier::Exact | MatchTier::TrailingWhitespace) =>
+        {
+            let reverted = splice_raw(&current, &spans[0]);
+            if let Err(e) = atomic_write(path, reverted) {
+                patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+            } else {
+                mark_undone(patch, None, root);
+            }
+        }
+        _ => patch.undo_conflict = Some(current),
+    }
+}
+
+/// Restores the backup after the user has reviewed the conflict diff.
+/// The overwritten content is kept in the journal so nothing is lost.
+pub fn force_undo_patch(patch: &mut PatchEntry, sandbox: &PathSandbox) {
+    let (Some(backup), Some(overwritten)) =
+        (patch.backup_content.clone(), patch.undo_conflict.take())
+    else {
+        return;
+    };
+    let path = match sandbox.resolve(&patch.data.file_path) {
+        Ok(p) => p,
+        Err(e) => {
+            patch.status = PatchStatus::Failed(format!("Path rejected: {}", e));
+            return;
+        }
+    };
+    let result = if patch.data.operation == PatchOperation::Create {
+        fs::remove_file(&path).map_err(|e| e.to_string())
+    } else {
+        match path.parent() {
+            Some(parent) => {
+                fs::create_dir_all(parent).map_err(|e| format!("creating folders: {}", e))
+            }
+            None => Ok(()),
+        }
+        .and_then(|_| atomic_write(&path, backup))
+    };
+    if let Err(e) = result {
+        patch.status = PatchStatus::Failed(format!("Undo failed: {}", e));
+    } else {
+        mark_undone(patch, Some(overwritten), &sandbox.root);
+    }
+}
+
+fn mark_undone(patch: &mut PatchEntry, overwritten: Option<String>, root: &Path) {
+    patch.status = PatchStatus::Pending;
+    patch.backup_content = None;
+    patch.post_hash = None;
+    patch.match_info = None;
+    patch.warning = None;
+    patch.undo_conflict = None;
+    journal_undone(root, patch, overwritten);
+}
+
+/// After a forced restore of `patches[index]`, later patches to the same file are no longer
+/// on disk. They go back to Pending so they can be re-applied.
+pub fn reset_later_patches(patches: &mut [PatchEntry], index: usize, sandbox: &PathSandbox) {
+    let file_path = patches[index].data.file_path.clone();
+    for later in patches[index + 1..].iter_mut() {
+        if later.data.file_path == file_path && later.status == PatchStatus::Success {
+            mark_undone(later, None, &sandbox.root);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Crash-safe file writes.
+
+use std::{fs, path::PathBuf};
+
+/// Writes `contents` to `path` without ever leaving a half-written file behind.
+/// The data goes to a temp file in the same directory, is fsynced, takes over the original
+/// file's permissions and is then renamed into place. A symlink is written through: the file
+/// it points to is replaced and the link stays. Errors name the step that failed.
+pub fn atomic_write(path: &std::path::Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    use std::io::Write;
+
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("'{}' is not a file path", path.display()))?
+        .to_string_lossy();
+    let original = fs::metadata(path).ok();
+    if original
+        .as_ref()
+        .is_some_and(|m| m.permissions().readonly())
+    {
+        return Err(format!("'{}' is read-only", path.display()));
+    }
+
+    let tmp_path = dir.join(format!(
+        ".{}.{}-{}.betterpaste.tmp",
+        file_name,
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    let result = (|| {
+        let mut tmp = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)
+            .map_err(|e| format!("creating temp file in '{}': {}", dir.display(), e))?;
+        tmp.write_all(contents.as_ref())
+            .map_err(|e| format!("writing temp file: {}", e))?;
+        tmp.sync_all()
+            .map_err(|e| format!("syncing temp file: {}", e))?;
+        if let Some(meta) = &original {
+            fs::set_permissions(&tmp_path, meta.permissions())
+                .map_err(|e| format!("copying permissions: {}", e))?;
+        }
+        fs::rename(&tmp_path, path).map_err(|e| format!("replacing '{}': {}", path.display(), e))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    } else if cfg!(unix) {
+        // Persist the rename itself; directories cannot be opened this way on Windows.
+        if let Ok(d) = fs::File::open(&dir) {
+            let _ = d.sync_all();
+        }
+    }
+    result
+}
@@ -0,0 +1,993 @@
+//! Structural compression for "Partial" files: signatures, types, fields and doc comments
+//! stay, function bodies become a placeholder.
+//!
+//! Brace languages are scanned with a small lexer that understands each language's comments
+//! and string literals, so braces inside them never unbalance the output. Python is split
+//! into logical lines and read by indentation. Both produce an outline of the file's
+//! symbols, which `render_file` turns into a context with each symbol full, reduced to its
+//! signature or left out.
+
+use std::{collections::HashMap, ops::Range, path::Path};
+
+/// Placeholder left inside the braces of a removed function body.
+pub const BODY_PLACEHOLDER: &str = "/* body removed */";
+
+/// Source languages `compress_code` understands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Rust,
+    Python,
+    /// TypeScript and JavaScript.
+    TypeScript,
+    Go,
+    CSharp,
+    /// Other brace languages with C-style comments: C, C++, Java, Kotlin, Swift and the like.
+    CLike,
+}
+
+impl Language {
+    /// Picks the language from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "rs" => Language::Rust,
+            "py" | "pyi" => Language::Python,
+            "ts" | "tsx" | "mts" | "cts" | "js" | "jsx" | "mjs" | "cjs" => Language::TypeScript,
+            "go" => Language::Go,
+            "cs" => Language::CSharp,
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "kt" | "kts" | "swift"
+            | "scala" | "dart" | "php" => Language::CLike,
+            _ => return None,
+        })
+    }
+}
+
+/// What a symbol in a file's outline declares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function, method, lambda or accessor with a body.
+    Function,
+    /// A struct, enum, trait, impl, class, interface, module or namespace. Its functions and
+    /// nested types are its children.
+    Type,
+}
+
+/// How much of a symbol goes into the context.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolMode {
+    #[default]
+    Full,
+    /// The declaration without its body. Members of a type default to this as well.
+    Signature,
+    /// Left out, together with its doc comments and attributes.
+    Excluded,
+}
+
+/// Symbol modes for one file, keyed by `Symbol::path`.
+pub type SymbolModes = HashMap<String, SymbolMode>;
+
+/// A function or type found in a source file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    /// The declared name, e.g. `len`, `Point`, `Server.Run` or `impl Display for Point`.
+    pub name: String,
+    /// The names from the outermost symbol down, joined by `::`. Unique within the file;
+    /// repeated names get a ` #2`, ` #3`, ... suffix.
+    pub path: String,
+    pub kind: SymbolKind,
+    /// Byte range of the whole declaration, from its doc comments and attributes to the end.
+    pub span: Range<usize>,
+    /// Byte range that `SymbolMode::Signature` replaces, if the symbol has a body to remove.
+    pub body: Option<Range<usize>>,
+    pub children: Vec<Symbol>,
+    /// What replaces `body`.
+    placeholder: String,
+}
+
+/// Compresses a source file for a partial context. Files in languages without a compressor
+/// are returned unchanged.
+pub fn compress_code(path: &Path, content: &str) -> String {
+    render_file(path, content, true, &SymbolModes::new())
+}
+
+/// Lists the functions and types declared in a file, in source order. Empty for languages
+/// without a compressor.
+pub fn outline_symbols(path: &Path, content: &str) -> Vec<Symbol> {
+    let mut symbols = match Language::from_path(path) {
+        Some(Language::Python) => outline_python(content),
+        Some(language) => outline_braces(content, language),
+        None => Vec::new(),
+    };
+    assign_paths(&mut symbols, "");
+    symbols
+}
+
+/// Renders a file with each symbol in its mode from `modes`. Symbols without an entry take
+/// the mode of the type around them; top-level ones are `Signature` in partial files and
+/// `Full` otherwise.
+pub fn render_file(path: &Path, content: &str, partial: bool, modes: &SymbolModes) -> String {
+    if !partial && modes.is_empty() {
+        return content.to_string();
+    }
+    let default = if partial {
+        SymbolMode::Signature
+    } else {
+        SymbolMode::Full
+    };
+    let symbols = outline_symbols(path, content);
+    let mut edits = Vec::new();
+    collect_edits(&symbols, content, modes, default, &mut edits);
+
+    let mut out = String::with_capacity(content.len());
+    let mut copied = 0;
+    for (range, replacement) in edits {
+        out.push_str(&content[copied..range.start]);
+        out.push_str(replacement);
+        copied = range.end;
+    }
+    out.push_str(&content[copied..]);
+    out
+}
+
+fn assign_paths(symbols: &mut [Symbol], parent: &str) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for symbol in symbols {
+        let mut path = if parent.is_empty() {
+            symbol.name.clone()
+        } else {
+            format!("{parent}::{}", symbol.name)
+        };
+        let count = seen.entry(path.clone()).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            path = format!("{path} #{count}");
+        }
+        symbol.path = path;
+        assign_paths(&mut symbol.children, &symbol.path);
+    }
+}
+
+/// Collects the replacements for `symbols` in source order.
+fn collect_edits<'a>(
+    symbols: &'a [Symbol],
+    content: &str,
+    modes: &SymbolModes,
+    inherited: SymbolMode,
+    edits: &mut Vec<(Range<usize>, &'a str)>,
+) {
+    for symbol in symbols {
+        let mode = modes.get(&symbol.path).copied().unwrap_or(inherited);
+        match mode {
+            SymbolMode::Excluded => edits.push((whole_lines(content, &symbol.span), "")),
+            _ if symbol.kind == SymbolKind::Type => {
+                collect_edits(&symbol.children, content, modes, mode, edits)
+            }
+            SymbolMode::Signature => {
+                if let Some(body) = &symbol.body {
+                    edits.push((body.clone(), &symbol.placeholder));
+                }
+            }
+            SymbolMode::Full => {}
+        }
+    }
+}
+
+/// Widens `span` to the lines it covers when nothing else is on them, so a left-out symbol
+/// does not leave its indentation behind.
+fn whole_lines(content: &str, span: &Range<usize>) -> Range<usize> {
+    let line_start = content[..span.start].rfind('\n').map_or(0, |p| p + 1);
+    let line_end = if content[..span.end].ends_with('\n') {
+        span.end
+    } else {
+        content[span.end..]
+            .find('\n')
+            .map_or(content.len(), |p| span.end + p + 1)
+    };
+    if content[line_start..span.start].trim().is_empty()
+        && content[span.end..line_end].trim().is_empty()
+    {
+        line_start..line_end
+    } else {
+        span.clone()
+    }
+}
+
+/// Keywords that open a block which is not a function body.
+const CONTROL_KEYWORDS: &[&str] = &[
+    "if",
+    "else",
+    "for",
+    "foreach",
+    "while",
+    "do",
+    "switch",
+    "case",
+    "try",
+    "catch",
+    "finally",
+    "using",
+    "lock",
+    "fixed",
+    "unsafe",
+    "checked",
+    "unchecked",
+    "synchronized",
+    "with",
+    "match",
+    "loop",
+    "return",
+    "new",
+];
+
+/// C# accessors, whose blocks are bodies even without a parameter list.
+const ACCESSORS: &[&str] = &["get", "set", "init", "add", "remove"];
+
+fn outline_braces(src: &str, language: Language) -> Vec<Symbol> {
+    let bytes = src.as_bytes();
+    let mut symbols = Vec::new();
+    // Code (without comments and literals) since the last `;`, `{` or `}` at this level.
+    let mut header = String::new();
+    // Where the declaration being read starts, including its comments and attributes.
+    let mut start: Option<usize> = None;
+    let mut parens = 0usize;
+    // Per open block: the paren depth outside it, the type it declares and what it contains.
+    let mut blocks: Vec<(usize, Option<Symbol>, Vec<Symbol>)> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if start.is_none()
+            && !bytes[i].is_ascii_whitespace()
+            && !matches!(bytes[i], b';' | b',' | b'}')
+        {
+            start = Some(i);
+        }
+        if let Some(end) = skip_literal(bytes, i, language) {
+            // Comments all start with `/`; everything else is a literal.
+            if bytes[i] != b'/' {
+                header.push_str("\"\"");
+            }
+            i = end;
+            continue;
+        }
+        match bytes[i] {
+            b'(' | b'[' => parens += 1,
+            b')' | b']' => parens = parens.saturating_sub(1),
+            b'{' if parens > 0 && !is_callback_header(&header, language) => {
+                // A brace inside parentheses is an expression or an inline type, not a block.
+                if let Some(close) = matching_brace(bytes, i, language) {
+                    header.push_str("{}");
+                    i = close + 1;
+                    continue;
+                }
+            }
+            b'{' => {
+                if parens == 0 && is_function_header(&header, language) {
+                    if let Some(close) = matching_brace(bytes, i, language) {
+                        let end = declaration_end(bytes, close + 1);
+                        innermost(&mut blocks, &mut symbols).push(Symbol {
+                            name: function_name(&header, language),
+                            path: String::new(),
+                            kind: SymbolKind::Function,
+                            span: start.unwrap_or(i)..end,
+                            body: Some(i + 1..close),
+                            children: Vec::new(),
+                            placeholder: format!(" {BODY_PLACEHOLDER} "),
+                        });
+                        i = end;
+                        header.clear();
+                        start = None;
+                        continue;
+                    }
+                }
+                // A kept block: a type, module, class or a callback such as an IIFE.
+                let declared = (parens == 0)
+                    .then(|| type_name(&header, language))
+                    .flatten()
+                    .map(|name| Symbol {
+                        name,
+                        path: String::new(),
+                        kind: SymbolKind::Type,
+                        span: start.unwrap_or(i)..i,
+                        body: None,
+                        children: Vec::new(),
+                        placeholder: String::new(),
+                    });
+                blocks.push((parens, declared, Vec::new()));
+                parens = 0;
+                header.clear();
+                start = None;
+                i += 1;
+                continue;
+            }
+            b'}' => {
+                i += 1;
+                if let Some((outer, declared, inner)) = blocks.pop() {
+                    parens = outer;
+                    let parent = innermost(&mut blocks, &mut symbols);
+                    match declared {
+                        Some(mut symbol) => {
+                            i = declaration_end(bytes, i);
+                            symbol.span.end = i;
+                            symbol.children = inner;
+                            parent.push(symbol);
+                        }
+                        None => parent.extend(inner),
+                    }
+                }
+                header.clear();
+                start = None;
+                continue;
+            }
+            b';' if parens == 0 => {
+                header.clear();
+                start = None;
+                i += 1;
+                continue;
+            }
+            // Go ends statements at line breaks, and a blank line ends a declaration
+            // everywhere (a stray comment above it is not its doc comment).
+            b'\n'
+                if parens == 0
+                    && (language == Language::Go && !header.trim().is_empty()
+                        || src[i + 1..]
+                            .split('\n')
+                            .next()
+                            .is_some_and(|line| line.trim().is_empty())) =>
+            {
+                header.clear();
+                start = None;
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        header.push(if bytes[i].is_ascii() {
+            bytes[i] as char
+        } else {
+            'x'
+        });
+        i += 1;
+    }
+    // Blocks left open by a truncated file end with it.
+    while let Some((_, declared, inner)) = blocks.pop() {
+        let parent = innermost(&mut blocks, &mut symbols);
+        match declared {
+            Some(mut symbol) => {
+                symbol.span.end = src.len();
+                symbol.children = inner;
+                parent.push(symbol);
+            }
+            None => parent.extend(inner),
+        }
+    }
+    symbols
+}
+
+/// Where new symbols go: the innermost open block, or the top level.
+fn innermost<'a>(
+    blocks: &'a mut [(usize, Option<Symbol>, Vec<Symbol>)],
+    symbols: &'a mut Vec<Symbol>,
+) -> &'a mut Vec<Symbol> {
+    blocks.last_mut().map_or(symbols, |(_, _, inner)| inner)
+}
+
+/// Extends a declaration ending at `end` over a `;` or `,` right after it.
+fn declaration_end(bytes: &[u8], end: usize) -> usize {
+    let mut j = end;
+    while matches!(bytes.get(j), Some(b' ' | b'\t')) {
+        j += 1;
+    }
+    if matches!(bytes.get(j), Some(b';' | b',')) {
+        j + 1
+    } else {
+        end
+    }
+}
+
+/// Identifiers in `text` with their byte offsets.
+fn ident_words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut begin = None;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        let is_ident = c.is_alphanumeric() || c == '_' || c == '$';
+        match (begin, is_ident) {
+            (None, true) => begin = Some(i),
+            (Some(b), false) => {
+                words.push((b, &text[b..i]));
+                begin = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn last_ident(text: &str) -> Option<String> {
+    ident_words(text).last().map(|(_, w)| w.to_string())
+}
+
+/// The name a function header declares, falling back to `<anonymous>`.
+fn function_name(header: &str, language: Language) -> String {
+    let header = strip_attributes(header.trim());
+    let words = ident_words(header);
+    // The name an arrow function or function expression is assigned to.
+    let assigned = || {
+        let eq = header
+            .find(['=', '('])
+            .filter(|&p| header[p..].starts_with('='))?;
+        last_ident(&header[..eq])
+    };
+    let name = match language {
+        Language::Rust => words
+            .iter()
+            .position(|(_, w)| *w == "fn")
+            .and_then(|p| words.get(p + 1))
+            .map(|(_, w)| w.to_string()),
+        Language::Go => match header.strip_prefix("func") {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                // `func (s *Server) Run(` names the method after its receiver type.
+                let receiver_end = rest.starts_with('(').then(|| rest.find(')')).flatten();
+                let name_part = receiver_end.map_or(rest, |end| &rest[end + 1..]);
+                let name = ident_words(name_part.split('(').next().unwrap_or_default())
+                    .first()
+                    .map(|(_, w)| w.to_string());
+                match (receiver_end.and_then(|end| last_ident(&rest[..end])), name) {
+                    (Some(receiver), Some(name)) => Some(format!("{receiver}.{name}")),
+                    (_, name) => name,
+                }
+            }
+            None => assigned(),
+        },
+        Language::Python => None,
+        Language::TypeScript | Language::CSharp | Language::CLike => {
+            let declared = words
+                .iter()
+                .find(|(_, w)| *w == "function")
+                .and_then(|(at, _)| {
+                    let rest = header[at + "function".len()..]
+                        .trim_start()
+                        .trim_start_matches('*');
+                    ident_words(rest)
+                        .first()
+                        .filter(|(p, _)| rest[..*p].trim().is_empty())
+                        .map(|(_, w)| w.to_string())
+                });
+            declared
+                .or_else(assigned)
+                .or_else(|| match header.find('(') {
+                    Some(open) => {
+                        let before = header[..open].trim_end();
+                        // `Foo<T>(` names `Foo`.
+                        let before = match before.strip_suffix('>') {
+                            Some(_) => before.rfind('<').map_or(before, |p| &before[..p]),
+                            None => before,
+                        };
+                        last_ident(before)
+                    }
+                    None => last_ident(header),
+                })
+        }
+    };
+    name.unwrap_or_else(|| "<anonymous>".to_string())
+}
+
+/// The name of the type, impl, module or namespace a block header declares, if it declares one.
+fn type_name(header: &str, language: Language) -> Option<String> {
+    let header = strip_attributes(header.trim());
+    let keywords: &[&str] = match language {
+        Language::Rust => &["struct", "enum", "union", "trait", "impl", "mod"],
+        Language::Go => &["type"],
+        Language::TypeScript => &["class", "interface", "enum", "namespace"],
+        Language::CSharp => &[
+            "class",
+            "struct",
+            "interface",
+            "enum",
+            "namespace",
+            "record",
+        ],
+        Language::CLike => &[
+            "class",
+            "struct",
+            "interface",
+            "enum",
+            "namespace",
+            "union",
+            "object",
+            "extension",
+            "protocol",
+        ],
+        Language::Python => &[],
+    };
+    let words = ident_words(header);
+    if words
+        .first()
+        .is_some_and(|(_, w)| CONTROL_KEYWORDS.contains(w))
+    {
+        return None;
+    }
+    let index = words.iter().position(|(_, w)| keywords.contains(w))?;
+    let (at, keyword) = words[index];
+    if keyword == "impl" {
+        return Some(
+            header[at..]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+    }
+    Some(words.get(index + 1).map_or(keyword, |(_, w)| w).to_string())
+}
+
+/// Whether the code before a `{` declares a function, method, lambda or accessor.
+fn is_function_header(header: &str, language: Language) -> bool {
+    let header = strip_attributes(header.trim());
+    let first_word = header
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .find(|w| !w.is_empty())
+        .unwrap_or_default();
+    let has_word = |word: &str| {
+        header
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .any(|w| w == word)
+    };
+    match language {
+        // `fn name`, not a `fn(..)` pointer type
+        Language::Rust => header
+            .split_whitespace()
+            .skip_while(|w| *w != "fn")
+            .nth(1)
+            .is_some_and(|name| name.starts_with(|c: char| c.is_alphabetic() || c == '_')),
+        // `func name`, `func (recv) name` or `= func(..)`
+        Language::Go => first_word == "func" || header.contains("= func"),
+        Language::Python => false,
+        Language::TypeScript | Language::CSharp | Language::CLike => {
+            if CONTROL_KEYWORDS.contains(&first_word) {
+                return false;
+            }
+            if header.ends_with("=>") || header.ends_with("->") {
+                return true;
+            }
+            if language == Language::TypeScript && has_word("function") {
+                return true;
+            }
+            if language == Language::CSharp {
+                let last_word = header.rsplit(' ').next().unwrap_or_default();
+                if ACCESSORS.contains(&last_word) && !header.contains('(') {
+                    return true;
+                }
+            }
+            is_method_header(header)
+        }
+    }
+}
+
+/// Whether a JavaScript function passed as an argument starts here, as in IIFEs and
+/// `describe(.., () => {` blocks. These are kept as containers so the functions declared
+/// inside them are compressed one by one.
+fn is_callback_header(header: &str, language: Language) -> bool {
+    if language != Language::TypeScript {
+        return false;
+    }
+    let header = header.trim_end();
+    header.ends_with("=>")
+        || header.rfind("function").is_some_and(|start| {
+            let signature = &header[start..];
+            signature.ends_with(')')
+                && signature.matches('(').count() == signature.matches(')').count()
+        })
+}
+
+/// `name(params) trailer` where the trailer is only a return type, `throws`/`where` clause,
+/// qualifiers or a constructor initializer, and nothing is assigned.
+fn is_method_header(header: &str) -> bool {
+    let Some(close) = header.rfind(')') else {
+        return false;
+    };
+    let trailer = &header[close + 1..];
+    let trailer_ok = trailer
+        .chars()
+        .all(|c| c.is_alphanumeric() || c.is_whitespace() || "_:<>[],.?|&*'()\"".contains(c));
+    let mut depth = 0i32;
+    let mut assigns = false;
+    let chars: Vec<char> = header.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => depth -= 1,
+            '=' if depth <= 0 => {
+                let prev = i.checked_sub(1).map(|p| chars[p]);
+                let next = chars.get(i + 1);
+                if !matches!(prev, Some('=' | '!' | '<' | '>')) && !matches!(next, Some('=' | '>'))
+                {
+                    assigns = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    trailer_ok && !assigns && header[..close].contains('(')
+}
+
+/// Drops leading `#[...]` (Rust) and `[...]` (C#) attributes so they do not hide the
+/// declaration's first word.
+fn strip_attributes(mut header: &str) -> &str {
+    loop {
+        let rest = header.strip_prefix('#').unwrap_or(header);
+        if !rest.starts_with('[') {
+            return header;
+        }
+        let mut depth = 0;
+        let Some(end) = rest.char_indices().find_map(|(i, c)| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        }) else {
+            return header;
+        };
+        header = rest[end + 1..].trim_start();
+    }
+}
+
+/// Index of the `}` closing the `{` at `open`, skipping comments and literals.
+fn matching_brace(bytes: &[u8], open: usize, language: Language) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = open;
+    while i < bytes.len() {
+        if let Some(end) = skip_literal(bytes, i, language) {
+            i = end;
+            continue;
+        }
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// If a comment or string/char literal starts at `i`, returns the index just after it.
+fn skip_literal(bytes: &[u8], i: usize, language: Language) -> Option<usize> {
+    let at = |j: usize| bytes.get(j).copied();
+    let before = |j: usize| j.checked_sub(1).and_then(at);
+    let find_from = |from: usize, needle: &[u8]| {
+        bytes[from.min(bytes.len())..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map_or(bytes.len(), |p| from + p + needle.len())
+    };
+    match bytes[i] {
+        b'/' if at(i + 1) == Some(b'/') => Some(
+            bytes[i..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |p| i + p),
+        ),
+        b'/' if at(i + 1) == Some(b'*') => {
+            if language != Language::Rust {
+                return Some(find_from(i + 2, b"*/"));
+            }
+            // Rust block comments nest.
+            let mut depth = 0;
+            let mut j = i;
+            while j + 1 < bytes.len() {
+                if bytes[j] == b'/' && bytes[j + 1] == b'*' {
+                    depth += 1;
+                    j += 2;
+                } else if bytes[j] == b'*' && bytes[j + 1] == b'/' {
+                    depth -= 1;
+                    j += 2;
+                    if depth == 0 {
+                        return Some(j);
+                    }
+                } else {
+                    j += 1;
+                }
+            }
+            Some(bytes.len())
+        }
+        b'r' if language == Language::Rust
+            && (!is_ident_byte(before(i))
+                || before(i) == Some(b'b') && !is_ident_byte(before(i - 1))) =>
+        {
+            // r"...", r#"..."# and their byte string forms
+            let mut j = i + 1;
+            while at(j) == Some(b'#') {
+                j += 1;
+            }
+            if at(j) != Some(b'"') {
+                return None;
+            }
+            let mut closing = vec![b'"'];
+            closing.extend(std::iter::repeat_n(b'#', j - i - 1));
+            Some(find_from(j + 1, &closing))
+        }
+        b'@' if language == Language::CSharp && at(i + 1) == Some(b'"') => {
+            // Verbatim string: "" is an escaped quote.
+            let mut j = i + 2;
+            while j < bytes.len() {
+                if bytes[j] == b'"' {
+                    if at(j + 1) == Some(b'"') {
+                        j += 2;
+                        continue;
+                    }
+                    return Some(j + 1);
+                }
+                j += 1;
+            }
+            Some(bytes.len())
+        }
+        b'"' if language == Language::CSharp && bytes[i..].starts_with(b"\"\"\"") => {
+            Some(find_from(i + 3, b"\"\"\""))
+        }
+        b'"' => Some(skip_quoted(bytes, i, b'"')),
+        b'\'' if language == Language::Rust => {
+            // A char literal, unless this is a lifetime or loop label.
+            if at(i + 1) == Some(b'\\') {
+                Some(skip_quoted(bytes, i, b'\''))
+            } else {
+                let len = match at(i + 1)? {
+                    0x00..=0x7f => 1,
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    _ => 4,
+                };
+                (at(i + 1 + len) == Some(b'\'')).then_some(i + len + 2)
+            }
+        }
+        b'\'' => Some(skip_quoted(bytes, i, b'\'')),
+        b'`' if language == Language::Go => Some(find_from(i + 1, b"`")),
+        b'`' if language == Language::TypeScript => Some(skip_template(bytes, i)),
+        _ => None,
+    }
+}
+
+fn is_ident_byte(b: Option<u8>) -> bool {
+    b.is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Skips a quoted literal with backslash escapes. Stops at a newline for unterminated quotes.
+fn skip_quoted(bytes: &[u8], i: usize, quote: u8) -> usize {
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b'\n' if quote == b'\'' => return j,
+            b if b == quote => return j + 1,
+            _ => j += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Skips a JavaScript template literal, including `${...}` expressions with nested braces.
+fn skip_template(bytes: &[u8], i: usize) -> usize {
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b'`' => return j + 1,
+            b'$' if bytes.get(j + 1) == Some(&b'{') => {
+                match matching_brace(bytes, j + 1, Language::TypeScript) {
+                    Some(close) => j = close + 1,
+                    None => return bytes.len(),
+                }
+            }
+            _ => j += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn outline_python(src: &str) -> Vec<Symbol> {
+    let lines: Vec<&str> = src.split_inclusive('\n').collect();
+    let starts = python_logical_starts(&lines);
+    // Byte offset of each line, and of the end of the file.
+    let offsets: Vec<usize> = std::iter::once(0)
+        .chain(lines.iter().scan(0, |offset, line| {
+            *offset += line.len();
+            Some(*offset)
+        }))
+        .collect();
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+    let is_blank = |line: &str| {
+        let t = line.trim();
+        t.is_empty() || t.starts_with('#')
+    };
+    // The last line of the logical line starting at `i`.
+    let logical_end = |i: usize| {
+        let mut j = i;
+        while j + 1 < lines.len() && !starts[j + 1] {
+            j += 1;
+        }
+        j
+    };
+    // The line after the block that follows a header at `indent`, starting at `body_start`.
+    let block_end = |body_start: usize, indent: usize| {
+        // The block runs until the next code line that is not indented deeper.
+        let mut end = body_start;
+        while end < lines.len()
+            && (!starts[end] || is_blank(lines[end]) || indent_of(lines[end]) > indent)
+        {
+            end += 1;
+        }
+        // Blank lines and comments before the next statement belong to what follows.
+        while end > body_start
+            && starts[end - 1]
+            && is_blank(lines[end - 1])
+            && (lines[end - 1].trim().is_empty() || indent_of(lines[end - 1]) <= indent)
+        {
+            end -= 1;
+        }
+        end
+    };
+
+    let mut symbols = Vec::new();
+    // Open classes with the line their body ends before.
+    let mut classes: Vec<(usize, Symbol)> = Vec::new();
+    // Start of the decorators above the next definition.
+    let mut decorated: Option<usize> = None;
+    let close_class = |classes: &mut Vec<(usize, Symbol)>, symbols: &mut Vec<Symbol>| {
+        if let Some((_, class)) = classes.pop() {
+            classes
+                .last_mut()
+                .map_or(symbols, |(_, parent)| &mut parent.children)
+                .push(class);
+        }
+    };
+    let mut i = 0;
+    while i < lines.len() {
+        while classes.last().is_some_and(|(end, _)| *end <= i) {
+            close_class(&mut classes, &mut symbols);
+        }
+        if !starts[i] || is_blank(lines[i]) {
+            i += 1;
+            continue;
+        }
+        let trimmed = lines[i].trim_start();
+        let header_end = logical_end(i);
+        if trimmed.starts_with('@') {
+            decorated.get_or_insert(offsets[i]);
+            i = header_end + 1;
+            continue;
+        }
+        let start = decorated.take().unwrap_or(offsets[i]);
+        let definition = trimmed
+            .strip_prefix("def ")
+            .or_else(|| trimmed.strip_prefix("async def "))
+            .map(|rest| (SymbolKind::Function, rest))
+            .or_else(|| {
+                trimmed
+                    .strip_prefix("class ")
+                    .map(|rest| (SymbolKind::Type, rest))
+            });
+        let Some((kind, rest)) = definition else {
+            i = header_end + 1;
+            continue;
+        };
+        let name = rest
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let indent = indent_of(lines[i]);
+        let body_start = header_end + 1;
+        let body_end = block_end(body_start, indent);
+        let mut symbol = Symbol {
+            name,
+            path: String::new(),
+            kind,
+            span: start..offsets[body_end],
+            body: None,
+            children: Vec::new(),
+            placeholder: String::new(),
+        };
+        if kind == SymbolKind::Type {
+            classes.push((body_end, symbol));
+            i = body_start;
+            continue;
+        }
+
+        // A one-line def, or a header with no indented body, has nothing to remove.
+        if let Some(first) = (body_start..body_end).find(|&j| starts[j] && !is_blank(lines[j])) {
+            let keep_until = if is_docstring(lines[first].trim_start()) {
+                logical_end(first) + 1
+            } else {
+                first
+            };
+            if keep_until < body_end {
+                symbol.body = Some(offsets[keep_until]..offsets[body_end]);
+                symbol.placeholder = format!(
+                    "{}...  # body removed{}",
+                    &lines[first][..indent_of(lines[first])],
+                    if lines[body_end - 1].ends_with("\r\n") {
+                        "\r\n"
+                    } else {
+                        "\n"
+                    }
+                );
+            }
+        }
+        classes
+            .last_mut()
+            .map_or(&mut symbols, |(_, class)| &mut class.children)
+            .push(symbol);
+        i = body_end;
+    }
+    while !classes.is_empty() {
+        close_class(&mut classes, &mut symbols);
+    }
+    symbols
+}
+
+/// Whether a statement is a plain string literal, i.e. a docstring when it comes first.
+fn is_docstring(statement: &str) -> bool {
+    let rest = statement.trim_start_matches(['r', 'R', 'u', 'U']);
+    rest.starts_with('"') || rest.starts_with('\'')
+}
+
+/// For each line, whether it starts a new logical line (is not inside brackets, a string or
+/// a backslash continuation from the line before).
+fn python_logical_starts(lines: &[&str]) -> Vec<bool> {
+    let mut starts = Vec::with_capacity(lines.len());
+    let mut depth = 0usize;
+    let mut string: Option<&'static str> = None;
+    let mut continued = false;
+    for line in lines {
+        starts.push(depth == 0 && string.is_none() && !continued);
+        continued = false;
+        let bytes = line.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if let Some(quote) = string {
+                if bytes[i] == b'\\' {
+                    i += 2;
+                    continue;
+                }
+                if bytes[i..].starts_with(quote.as_bytes()) {
+                    string = None;
+                    i += quote.len();
+                    continue;
+                }
+                if bytes[i] == b'\n' && quote.len() == 1 {
+                    string = None;
+                }
+                i += 1;
+                continue;
+            }
+            match bytes[i] {
+                b'#' => break,
+                b'"' | b'\'' => {
+                    let quote = match bytes[i] {
+                        b'"' if bytes[i..].starts_with(b"\"\"\"") => "\"\"\"",
+                        b'\'' if bytes[i..].starts_with(b"'''") => "'''",
+                        b'"' => "\"",
+                        _ => "'",
+                    };
+                    string = Some(quote);
+                    i += quote.len();
+                    continue;
+                }
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+                b'\\' if line[i + 1..].trim().is_empty() => continued = true,
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+    starts
+}
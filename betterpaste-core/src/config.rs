@@ -0,0 +1,187 @@
+//! User-editable settings shared by the GUI, the server and the CLI.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Header that carries `AppConfig::api_token` on requests to the local server.
+pub const API_TOKEN_HEADER: &str = "X-BetterPaste-Token";
+
+/// How many project roots the recent-projects list keeps.
+pub const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Context window of one target model, for the Generator's token budget.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelBudget {
+    pub model: String,
+    pub max_tokens: usize,
+}
+
+/// Settings as saved in the per-user `betterPaste/config.json`. Missing fields take their defaults.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AppConfig {
+    pub port: u16,
+    pub instructions: String,
+    pub replacing_rules: String,
+    pub example: String,
+    pub about_content: String,
+    pub journal_max_age_days: u32,
+    pub journal_max_size_mb: u64,
+    /// Globs (relative to the project root) that patches may touch. Empty allows everything.
+    pub patch_allow_globs: Vec<String>,
+    /// Globs that patches may never touch, checked before the allow list.
+    pub patch_deny_globs: Vec<String>,
+    /// Project roots opened before, most recent first.
+    pub recent_projects: Vec<PathBuf>,
+    /// Project roots open as workspaces in the GUI, reopened on the next start.
+    pub open_projects: Vec<PathBuf>,
+    pub model_budgets: Vec<ModelBudget>,
+    /// Name of the entry in `model_budgets` the context is sized for.
+    pub target_model: String,
+    /// Switch the largest files to partial instead of only warning when over budget.
+    pub auto_partial: bool,
+    /// Files larger than this are left out of contexts by default. 0 disables the cap.
+    pub max_file_size_kb: u64,
+    /// Globs of generated or vendored files (lockfiles, bundles, generated code) that are
+    /// left out of contexts by default.
+    pub generated_globs: Vec<String>,
+    /// Shared secret every request to the local server must carry in the
+    /// [`API_TOKEN_HEADER`] header. Generated on first launch.
+    pub api_token: String,
+    /// Browser origins (`scheme://host[:port]`) allowed to call the local server.
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            port: 3030,
+            instructions: "This file is a consolidated version of the codebase.\nThe organization of the content is as follows:\nOverview\nReplacingRules\nFileStructure\nFiles".to_string(),
+            replacing_rules: "Files sent with compressed=\"true\" have their function bodies replaced by /* body removed */ (or ...  # body removed in Python). If the AI needs one of those bodies, ask the user.\n\nSTRICT FORMATTING RULES:\n1. **DO NOT** put markdown code fences (```) *inside* the search/replace tags. It will cause a mismatch.\n2. **DO** wrap the ENTIRE block (from START to END) in a single code block for readability (e.g. ```rust).\n3. Whitespace Critical: The SEARCH section is used for an exact string match. You MUST copy the search text exactly from the source, preserving all indentation and newlines.\n4. File Operations: To add a new file use a CREATE block: the path, the CONTENT marker, then the full file content. To remove a file use a DELETE block holding only the path. To move or rename a file use a RENAME block: the old path, the TO marker, then the new path. Every block closes with the END marker, written exactly as in the example below. Never use SEARCH/REPLACE to create a file.".to_string(),
+            example: "```rust\n[<(x{START}x)>]\nmesh_core/src/main.rs\n[<(x{SEARCH}x)>]\npub struct GuardResponse {\n    pub success: bool,\n    pub message: String,\n}\n[<(x{REPLACEWITH}x)>]\npub struct GuardResponse {\n    pub is_admin: bool,\n    pub success: bool,\n    pub message: String,\n}\n[<(x{END}x)>]\n```\n\nCreating, deleting and renaming files:\n```rust\n[<(x{CREATE}x)>]\nmesh_core/src/guard.rs\n[<(x{CONTENT}x)>]\npub fn is_admin(id: u32) -> bool {\n    id == 0\n}\n[<(x{END}x)>]\n\n[<(x{DELETE}x)>]\nmesh_core/src/old_guard.rs\n[<(x{END}x)>]\n\n[<(x{RENAME}x)>]\nmesh_core/src/util.rs\n[<(x{TO}x)>]\nmesh_core/src/helpers/util.rs\n[<(x{END}x)>]\n```".to_string(),
+            about_content: "# About BetterPaste\n\nBetterPaste is a tool to bridge your local codebase with AI Chat interfaces.\n# Made by\nMyros".to_string(),
+            journal_max_age_days: 30,
+            journal_max_size_mb: 50,
+            patch_allow_globs: vec!["**".to_string()],
+            patch_deny_globs: vec![
+                "**/.env".to_string(),
+                "**/*.pem".to_string(),
+                "**/*.key".to_string(),
+            ],
+            recent_projects: Vec::new(),
+            open_projects: Vec::new(),
+            model_budgets: [
+                ("GPT-4o", 128_000),
+                ("Claude", 200_000),
+                ("Gemini", 1_000_000),
+                ("DeepSeek", 64_000),
+            ]
+            .into_iter()
+            .map(|(model, max_tokens)| ModelBudget { model: model.to_string(), max_tokens })
+            .collect(),
+            target_model: "GPT-4o".to_string(),
+            auto_partial: false,
+            max_file_size_kb: 1024,
+            generated_globs: [
+                "**/Cargo.lock",
+                "**/package-lock.json",
+                "**/yarn.lock",
+                "**/pnpm-lock.yaml",
+                "**/poetry.lock",
+                "**/Pipfile.lock",
+                "**/Gemfile.lock",
+                "**/composer.lock",
+                "**/go.sum",
+                "**/*.min.js",
+                "**/*.min.css",
+                "**/*.map",
+                "**/*.pb.go",
+                "**/*_pb2.py",
+                "**/*.g.dart",
+                "**/*.designer.cs",
+                "**/vendor/**",
+                "**/node_modules/**",
+                "**/third_party/**",
+                "**/dist/**",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            api_token: String::new(),
+            allowed_origins: [
+                "https://chatgpt.com",
+                "https://claude.ai",
+                "https://gemini.google.com",
+                "https://aistudio.google.com",
+                "https://chat.deepseek.com",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Token budget of the target model, if it is in the list.
+    pub fn token_budget(&self) -> Option<usize> {
+        self.model_budgets
+            .iter()
+            .find(|b| b.model == self.target_model)
+            .map(|b| b.max_tokens)
+    }
+
+    /// Generates `api_token` if there is none yet. Returns whether the config changed and
+    /// needs saving.
+    pub fn ensure_api_token(&mut self) -> bool {
+        if !self.api_token.is_empty() {
+            return false;
+        }
+        let mut bytes = [0u8; 24];
+        if let Err(e) = getrandom::fill(&mut bytes) {
+            // Without OS randomness there is no safe token; the server then rejects everything.
+            eprintln!("Failed to generate an API token: {}", e);
+            return false;
+        }
+        self.api_token = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        true
+    }
+
+    /// Whether a request's token matches `api_token`. Compares in constant time, and never
+    /// matches while no token is set.
+    pub fn token_matches(&self, presented: &str) -> bool {
+        let expected = self.api_token.as_bytes();
+        let presented = presented.as_bytes();
+        !expected.is_empty()
+            && expected.len() == presented.len()
+            && expected
+                .iter()
+                .zip(presented)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    /// Whether a request with this `Origin` header may reach the server. Requests without
+    /// one (the userscript's `GM_xmlhttpRequest`, curl, the CLI) are allowed; web pages and
+    /// browser extensions only when their origin (e.g. `chrome-extension://<id>`) is in
+    /// `allowed_origins`. The server's CORS answers use the same check.
+    pub fn origin_allowed(&self, origin: Option<&str>) -> bool {
+        match origin {
+            None => true,
+            Some(origin) => self.allowed_origins.iter().any(|allowed| {
+                allowed
+                    .trim()
+                    .trim_end_matches('/')
+                    .eq_ignore_ascii_case(origin)
+            }),
+        }
+    }
+
+    /// Moves `root` to the front of the recent-projects list, dropping the oldest entries
+    /// beyond [`MAX_RECENT_PROJECTS`].
+    pub fn remember_project(&mut self, root: &Path) {
+        self.recent_projects.retain(|p| p != root);
+        self.recent_projects.insert(0, root.to_path_buf());
+        self.recent_projects.truncate(MAX_RECENT_PROJECTS);
+    }
+}
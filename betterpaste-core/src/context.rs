@@ -0,0 +1,241 @@
+//! Building the XML context for the AI, and unpacking it again (the Ungenerator).
+
+use crate::atomic::atomic_write;
+use crate::compress::{SymbolMode, SymbolModes, render_file};
+use crate::config::AppConfig;
+use crate::detect::{SkipReason, detect_skips};
+use crate::presets::Preset;
+use crate::sandbox::PathSandbox;
+use crate::tokens::{context_tokens, count_file_tokens, fit_to_budget};
+use ignore::WalkBuilder;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A Generator selection, published by the GUI so the server can build the same context.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContextSelection {
+    pub selected: HashMap<PathBuf, bool>,
+    pub partials: HashMap<PathBuf, bool>,
+    pub symbols: HashMap<PathBuf, SymbolModes>,
+    /// Preset whose instructions and rules stand in for the configured ones.
+    pub preset: Option<String>,
+}
+
+/// Lists the files under `root` that can go into a context, relative to `root` and sorted.
+/// Honors `.gitignore` and skips BetterPaste's own config and journal.
+pub fn scan_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .git_ignore(true)
+        .filter_entry(|entry| entry.file_name() != ".betterpaste")
+        .build();
+
+    for result in walker {
+        match result {
+            Ok(entry) => {
+                if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+                    // Filter out binaries and config
+                    if let Some(file_name) = entry.file_name().to_str() {
+                        if file_name == "betterPaste_config.json" || file_name.ends_with(".exe") {
+                            continue;
+                        }
+                    }
+                    let path = entry
+                        .path()
+                        .strip_prefix(root)
+                        .unwrap_or(entry.path())
+                        .to_path_buf();
+                    files.push(path);
+                }
+            }
+            Err(err) => eprintln!("Error scanning file: {}", err),
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Builds the XML context: instructions, patch rules, the file list and the selected files,
+/// with `partials` compressed and the symbols in `symbols` rendered in their mode (see
+/// `render_file`). `files` are relative to `root`. Selected files that are not UTF-8 text
+/// or cannot be read appear as an empty `<File … skipped="binary|unreadable"/>` entry.
+pub fn generate_xml(
+    root: &Path,
+    files: &[PathBuf],
+    selected: &HashMap<PathBuf, bool>,
+    partials: &HashMap<PathBuf, bool>,
+    symbols: &HashMap<PathBuf, SymbolModes>,
+    config: &AppConfig,
+) -> String {
+    let no_modes = SymbolModes::new();
+    let mut xml = String::new();
+
+    xml.push_str("<Overview>\n<Instructions>\n");
+    xml.push_str(&config.instructions);
+    xml.push_str("\n</Instructions>\n</Overview>\n");
+
+    xml.push_str("<ReplacingRules>\n<instructions>\n");
+    xml.push_str(&config.replacing_rules);
+    xml.push_str("\n</instructions>\n<Example>\n");
+    xml.push_str(&config.example);
+    xml.push_str("\n</Example>\n</ReplacingRules>\n");
+
+    xml.push_str("<FileStructure>\n");
+    for file in files {
+        if *selected.get(file).unwrap_or(&false) {
+            xml.push_str(&format!("{}\n", file.display()));
+        }
+    }
+    xml.push_str("</FileStructure>\n<Files>\n");
+    for file in files {
+        if *selected.get(file).unwrap_or(&false) {
+            let is_partial = *partials.get(file).unwrap_or(&false);
+            let modes = symbols.get(file).unwrap_or(&no_modes);
+            match fs::read_to_string(root.join(file)) {
+                Ok(content) => {
+                    let final_content = render_file(file, &content, is_partial, modes);
+                    let compressed = is_partial || modes.values().any(|m| *m != SymbolMode::Full);
+                    xml.push_str(&format!(
+                        "<File path=\"{}\" compressed=\"{}\">\n",
+                        file.display(),
+                        compressed
+                    ));
+                    xml.push_str(&final_content);
+                    xml.push_str("\n</File>\n");
+                }
+                // Keep a placeholder so the AI (and the user) can see the file was left out.
+                Err(e) => {
+                    let reason = if e.kind() == std::io::ErrorKind::InvalidData {
+                        "binary"
+                    } else {
+                        "unreadable"
+                    };
+                    xml.push_str(&format!(
+                        "<File path=\"{}\" skipped=\"{}\"/>\n",
+                        file.display(),
+                        reason
+                    ));
+                }
+            }
+        }
+    }
+    xml.push_str("</Files>");
+    xml
+}
+
+/// A context built away from the Generator, for the server's context endpoint and the CLI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuiltContext {
+    /// Selected files.
+    pub files: usize,
+    /// Estimated size of `xml`.
+    pub tokens: usize,
+    /// Context window of the target model, if one is configured.
+    pub budget: Option<usize>,
+    /// Files the preset selected but `detect_skips` left out, sorted.
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    /// Files switched to partial to fit the budget, largest first.
+    pub fitted: Vec<PathBuf>,
+    pub xml: String,
+}
+
+/// Builds the context the Generator would for `root`: from `selection` when the GUI has
+/// published one, otherwise from `preset` (or every file) without the files `detect_skips`
+/// flags, unless `include_skipped`. The preset's instructions and rules stand in for the
+/// configured ones, and with `auto_partial` files are compressed until the context fits the
+/// target model's budget. Reads the selected files, so keep it off async threads.
+pub fn build_context(
+    root: &Path,
+    config: &AppConfig,
+    preset: Option<&Preset>,
+    selection: Option<ContextSelection>,
+    include_skipped: bool,
+) -> BuiltContext {
+    let config = preset.map_or(config.clone(), |p| p.apply_to(config));
+    let files = scan_files(root);
+    let mut skipped = Vec::new();
+    let (selected, mut partials, symbols) = match selection {
+        Some(selection) => (selection.selected, selection.partials, selection.symbols),
+        None => {
+            let (mut selected, partials) = preset.cloned().unwrap_or_default().selection(&files);
+            if !include_skipped {
+                for (file, reason) in detect_skips(root, &files, &config) {
+                    if selected.insert(file.clone(), false) == Some(true) {
+                        skipped.push((file, reason));
+                    }
+                }
+                skipped.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            (selected, partials, HashMap::new())
+        }
+    };
+
+    // Only the files that go into the context are read.
+    let emitted: Vec<PathBuf> = files
+        .iter()
+        .filter(|f| selected.get(*f).copied().unwrap_or(false))
+        .cloned()
+        .collect();
+    let tokens = count_file_tokens(root, &emitted, &symbols);
+    let budget = config.token_budget();
+    let fitted = match (budget, config.auto_partial) {
+        (Some(budget), true) => {
+            fit_to_budget(&files, &selected, &mut partials, &tokens, &config, budget)
+        }
+        _ => Vec::new(),
+    };
+    BuiltContext {
+        files: selected.values().filter(|on| **on).count(),
+        tokens: context_tokens(&files, &selected, &partials, &tokens, &config),
+        budget,
+        skipped,
+        fitted,
+        xml: generate_xml(root, &files, &selected, &partials, &symbols, &config),
+    }
+}
+
+/// What the Ungenerator does (or would do) with one `<File>` entry of a context XML.
+#[derive(Debug, PartialEq)]
+pub enum ExtractOutcome {
+    New,
+    Exists,
+    Rejected(String),
+    Failed(String),
+}
+
+/// Unpacks every `<File>` of a context XML into the sandbox. Existing files are never
+/// overwritten; with `dry_run` nothing is written and the outcomes are only reported.
+pub fn extract_files(
+    xml: &str,
+    sandbox: &PathSandbox,
+    dry_run: bool,
+) -> Vec<(String, ExtractOutcome)> {
+    let re = regex::Regex::new(
+        r#"(?ms)<File path="([^"]+)"(?: compressed="[^"]+")?>\s*(.*?)\s*</File>"#,
+    )
+    .unwrap();
+    let mut outcomes = Vec::new();
+    for caps in re.captures_iter(xml) {
+        let name = caps[1].to_string();
+        let outcome = match sandbox.resolve(&name) {
+            Err(e) => ExtractOutcome::Rejected(e),
+            Ok(path) if path.exists() => ExtractOutcome::Exists,
+            Ok(_) if dry_run => ExtractOutcome::New,
+            Ok(path) => {
+                if let Some(parent) = path.parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                match atomic_write(&path, caps[2].trim()) {
+                    Ok(()) => ExtractOutcome::New,
+                    Err(e) => ExtractOutcome::Failed(e),
+                }
+            }
+        };
+        outcomes.push((name, outcome));
+    }
+    outcomes
+}
@@ -0,0 +1,193 @@
+//! Spots files that should stay out of a context by default: binaries, files over the size
+//! cap, generated or vendored files and minified bundles. They are still listed, so the
+//! user can pick them anyway.
+
+use crate::config::AppConfig;
+use globset::{GlobBuilder, GlobMatcher};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// How much of each file is read to sniff its content.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Why a file is left out of the context unless the user selects it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SkipReason {
+    /// Contains NUL bytes or is not valid UTF-8.
+    Binary,
+    /// Larger than `AppConfig::max_file_size_kb`.
+    TooLarge { bytes: u64 },
+    /// Matches one of `AppConfig::generated_globs`, or is marked `linguist-generated` or
+    /// `linguist-vendored` in a `.gitattributes`. `rule` names the glob or attribute file.
+    Generated { rule: String },
+    /// Text with (almost) no line breaks, as in minified bundles.
+    Minified,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Binary => write!(f, "binary"),
+            SkipReason::TooLarge { bytes } => write!(f, "too large ({} KB)", bytes.div_ceil(1024)),
+            SkipReason::Generated { rule } => write!(f, "generated ({})", rule),
+            SkipReason::Minified => write!(f, "minified"),
+        }
+    }
+}
+
+/// Checks every file in `files` (relative to `root`) and returns the ones to leave out,
+/// with the reason. Files that cannot be read at all count as binary.
+pub fn detect_skips(
+    root: &Path,
+    files: &[PathBuf],
+    config: &AppConfig,
+) -> HashMap<PathBuf, SkipReason> {
+    let generated: Vec<(&String, GlobMatcher)> = config
+        .generated_globs
+        .iter()
+        .filter(|p| !p.trim().is_empty())
+        .filter_map(|p| {
+            globset::Glob::new(p.trim())
+                .ok()
+                .map(|g| (p, g.compile_matcher()))
+        })
+        .collect();
+    let attributes = GitAttributes::load(root, files);
+    let max_bytes = config.max_file_size_kb.saturating_mul(1024);
+
+    files
+        .iter()
+        .filter_map(|file| {
+            let reason = if let Some((glob, _)) = generated.iter().find(|(_, m)| m.is_match(file)) {
+                SkipReason::Generated {
+                    rule: glob.to_string(),
+                }
+            } else if let Some(rule) = attributes.generated(file) {
+                SkipReason::Generated { rule }
+            } else {
+                sniff(&root.join(file), max_bytes)?
+            };
+            Some((file.clone(), reason))
+        })
+        .collect()
+}
+
+/// Size, binary and minification checks on the file's metadata and first bytes.
+fn sniff(path: &Path, max_bytes: u64) -> Option<SkipReason> {
+    let Ok(mut file) = fs::File::open(path) else {
+        return Some(SkipReason::Binary);
+    };
+    let bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+    if max_bytes > 0 && bytes > max_bytes {
+        return Some(SkipReason::TooLarge { bytes });
+    }
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    if file
+        .by_ref()
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return Some(SkipReason::Binary);
+    }
+    if head.contains(&0) {
+        return Some(SkipReason::Binary);
+    }
+    // A multi-byte character cut off at the end of the sample is fine.
+    if let Err(e) = std::str::from_utf8(&head) {
+        if e.error_len().is_some() {
+            return Some(SkipReason::Binary);
+        }
+    }
+    let lines = head.iter().filter(|&&b| b == b'\n').count() + 1;
+    if head.len() >= 2048 && head.len() / lines > 500 {
+        return Some(SkipReason::Minified);
+    }
+    None
+}
+
+/// `linguist-generated` and `linguist-vendored` rules from every `.gitattributes` in the project.
+struct GitAttributes {
+    /// (matcher, set or unset, attribute file) in the order git applies them.
+    rules: Vec<(GlobMatcher, bool, String)>,
+}
+
+impl GitAttributes {
+    fn load(root: &Path, files: &[PathBuf]) -> Self {
+        let mut sources: Vec<&PathBuf> = files
+            .iter()
+            .filter(|f| f.ends_with(".gitattributes"))
+            .collect();
+        // Deeper files override shallower ones.
+        sources.sort_by_key(|f| f.components().count());
+        let mut rules = Vec::new();
+        for source in sources {
+            let Ok(content) = fs::read_to_string(root.join(source)) else {
+                continue;
+            };
+            let base = source
+                .parent()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            for line in content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            {
+                let mut parts = line.split_whitespace();
+                let Some(pattern) = parts.next() else {
+                    continue;
+                };
+                let Some(set) = parts.filter_map(linguist_attribute).next_back() else {
+                    continue;
+                };
+                // Patterns without a slash match at any depth below the attribute file.
+                let pattern = match pattern.strip_prefix('/') {
+                    Some(anchored) => anchored.to_string(),
+                    None if !pattern.contains('/') => format!("**/{}", pattern),
+                    None => pattern.to_string(),
+                };
+                let pattern = if base.is_empty() {
+                    pattern
+                } else {
+                    format!("{}/{}", base, pattern)
+                };
+                if let Ok(glob) = GlobBuilder::new(&pattern).literal_separator(true).build() {
+                    rules.push((glob.compile_matcher(), set, source.display().to_string()));
+                }
+            }
+        }
+        Self { rules }
+    }
+
+    /// The attribute file that marks `file` generated or vendored, if one does. Attribute
+    /// files themselves never are.
+    fn generated(&self, file: &Path) -> Option<String> {
+        if file.ends_with(".gitattributes") {
+            return None;
+        }
+        let (_, set, source) = self
+            .rules
+            .iter()
+            .rev()
+            .find(|(glob, _, _)| glob.is_match(file))?;
+        set.then(|| source.clone())
+    }
+}
+
+/// Whether a `.gitattributes` attribute sets (`true`) or unsets (`false`) one of the
+/// linguist generated/vendored flags.
+fn linguist_attribute(attribute: &str) -> Option<bool> {
+    let (name, value) = match attribute.split_once('=') {
+        Some((name, value)) => (name, value != "false"),
+        None => match attribute.strip_prefix(['-', '!']) {
+            Some(name) => (name, false),
+            None => (attribute, true),
+        },
+    };
+    matches!(name, "linguist-generated" | "linguist-vendored").then_some(value)
+}
@@ -0,0 +1,156 @@
+//! Patch lifecycle events for listeners outside the GUI. Every operation that changes a
+//! workspace's queue runs through [`track_changes`], which reports what that one operation
+//! did on the channel handed out by [`SharedAppState::subscribe`](crate::SharedAppState::subscribe).
+
+use crate::history::{PatchReceipt, ReceiptStatus, Workspace};
+use serde::Serialize;
+use std::sync::mpsc;
+
+/// What happened.
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// A patch was added to a workspace's list. Followed by `Queued`, `Applied` or `Failed`
+    /// when it did not stay pending, or by `Dismissed` when auto-dismiss dropped it.
+    Received,
+    Queued,
+    Applied,
+    Failed,
+    /// An applied patch is pending again.
+    Undone,
+    /// The patch left the list.
+    Dismissed,
+    Paused,
+    AutoApply,
+    AutoDismiss,
+}
+
+/// One change in one workspace. Patch events carry the patch's receipt, switch events
+/// the switch's new value.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct QueueEvent {
+    #[serde(rename = "event")]
+    pub kind: EventKind,
+    pub project: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<PatchReceipt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+/// The sending end of the event channel, shared by every open workspace. Empty until
+/// someone subscribes, and then tracking an operation is the only cost.
+#[derive(Clone, Debug, Default)]
+pub struct EventSender(Option<mpsc::Sender<QueueEvent>>);
+
+impl EventSender {
+    pub(crate) fn new(sender: mpsc::Sender<QueueEvent>) -> Self {
+        Self(Some(sender))
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub(crate) fn send(&self, event: QueueEvent) {
+        if let Some(sender) = &self.0 {
+            // A receiver that went away just means nobody listens any more.
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// The parts of a workspace that events are about.
+struct Snapshot {
+    paused: bool,
+    auto_apply: bool,
+    auto_dismiss: bool,
+    patches: Vec<PatchReceipt>,
+}
+
+impl Snapshot {
+    fn of(workspace: &Workspace) -> Self {
+        Self {
+            paused: workspace.is_paused,
+            auto_apply: workspace.auto_apply,
+            auto_dismiss: workspace.auto_dismiss,
+            patches: workspace.patches.iter().map(PatchReceipt::of).collect(),
+        }
+    }
+}
+
+/// Runs `change` on `workspace` and sends the events it caused: switch changes first, then
+/// dismissed patches, then new and changed patches in list order. Without a subscriber it
+/// only runs `change`. Nested calls report through the outermost one, so each operation
+/// is reported exactly once.
+pub fn track_changes<R>(workspace: &mut Workspace, change: impl FnOnce(&mut Workspace) -> R) -> R {
+    if !workspace.events.is_subscribed() {
+        return change(workspace);
+    }
+    let events = std::mem::take(&mut workspace.events);
+    let then = Snapshot::of(workspace);
+    let result = change(workspace);
+    workspace.events = events;
+
+    let now = Snapshot::of(workspace);
+    let send = |kind, patch: Option<&PatchReceipt>, enabled| {
+        workspace.events.send(QueueEvent {
+            kind,
+            project: workspace.id.clone(),
+            patch: patch.cloned(),
+            enabled,
+        });
+    };
+    for (kind, was, is) in [
+        (EventKind::Paused, then.paused, now.paused),
+        (EventKind::AutoApply, then.auto_apply, now.auto_apply),
+        (EventKind::AutoDismiss, then.auto_dismiss, now.auto_dismiss),
+    ] {
+        if was != is {
+            send(kind, None, Some(is));
+        }
+    }
+    for patch in &then.patches {
+        if !now.patches.iter().any(|p| p.id == patch.id) {
+            let dismissed = PatchReceipt {
+                status: ReceiptStatus::Dismissed,
+                reason: None,
+                ..patch.clone()
+            };
+            send(EventKind::Dismissed, Some(&dismissed), None);
+        }
+    }
+    for patch in &now.patches {
+        let old = then.patches.iter().find(|p| p.id == patch.id);
+        if old.is_none() {
+            send(EventKind::Received, Some(patch), None);
+        }
+        if old == Some(patch) {
+            continue;
+        }
+        let kind = match (old.map(|p| p.status), patch.status) {
+            (_, ReceiptStatus::Queued) => EventKind::Queued,
+            (_, ReceiptStatus::Success) => EventKind::Applied,
+            (_, ReceiptStatus::Failed) => EventKind::Failed,
+            (Some(ReceiptStatus::Success), ReceiptStatus::Pending) => EventKind::Undone,
+            _ => continue,
+        };
+        send(kind, Some(patch), None);
+    }
+    result
+}
+
+/// Reports patches that auto-dismiss dropped on arrival; they never enter the list, so
+/// [`track_changes`] cannot see them.
+pub(crate) fn report_dismissed_on_arrival(workspace: &Workspace, receipts: &[PatchReceipt]) {
+    for receipt in receipts {
+        for kind in [EventKind::Received, EventKind::Dismissed] {
+            workspace.events.send(QueueEvent {
+                kind,
+                project: workspace.id.clone(),
+                patch: Some(receipt.clone()),
+                enabled: None,
+            });
+        }
+    }
+}
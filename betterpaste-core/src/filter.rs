@@ -0,0 +1,56 @@
+//! The Generator's file filter: a glob, a regular expression or plain text.
+
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use std::path::Path;
+
+/// A parsed filter box. Paths are matched relative to the project root, with `/` separators.
+#[derive(Clone, Debug, Default)]
+pub enum FileFilter {
+    #[default]
+    All,
+    /// Paths containing the text, ignoring case.
+    Text(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl FileFilter {
+    /// Parses filter text. `re:...` and `/.../` are regular expressions, text with any of
+    /// `*?[{` is a glob, and anything else matches paths that contain it. Empty text
+    /// matches every path.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let pattern = text
+            .strip_prefix("re:")
+            .or_else(|| text.strip_prefix('/').and_then(|t| t.strip_suffix('/')));
+        if let Some(pattern) = pattern {
+            return Regex::new(pattern)
+                .map(FileFilter::Regex)
+                .map_err(|e| e.to_string());
+        }
+        if text.is_empty() {
+            Ok(FileFilter::All)
+        } else if text.contains(['*', '?', '[', '{']) {
+            Glob::new(text)
+                .map(|g| FileFilter::Glob(g.compile_matcher()))
+                .map_err(|e| e.to_string())
+        } else {
+            Ok(FileFilter::Text(text.to_lowercase()))
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        matches!(self, FileFilter::All)
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        match self {
+            FileFilter::All => true,
+            FileFilter::Text(text) => path.to_lowercase().contains(text),
+            FileFilter::Glob(glob) => glob.is_match(&path),
+            FileFilter::Regex(re) => re.is_match(&path),
+        }
+    }
+}
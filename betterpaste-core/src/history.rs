@@ -0,0 +1,650 @@
+//! The list of received patches, grouped by AI reply, and everything that acts on it.
+
+use crate::apply::{
+    apply_patch, apply_patch_with, force_undo_patch, reset_later_patches, undo_patch,
+};
+use crate::context::ContextSelection;
+use crate::events::{EventSender, report_dismissed_on_arrival, track_changes};
+use crate::journal::journal_dismissed;
+use crate::matching::find_matches;
+use crate::patch::{IncomingPatch, PatchEntry, PatchOperation, PatchStatus};
+use crate::sandbox::PathSandbox;
+use crate::text::normalized_body;
+use serde::Serialize;
+use std::{fs, path::PathBuf};
+
+/// All blocks from one AI reply, applied and undone together.
+pub struct ResponseGroup {
+    pub id: String,
+    pub timestamp: String,
+    /// Why the last group-level apply or undo stopped, if it did.
+    pub error: Option<String>,
+}
+
+/// One open project: its patch list, response groups and queue switches, confined to the
+/// sandbox's root.
+pub struct Workspace {
+    /// Unique among the open workspaces; incoming patches may name it to be routed here.
+    pub id: String,
+    pub patches: Vec<PatchEntry>,
+    pub groups: Vec<ResponseGroup>,
+    pub new_patch_alert: bool,
+    pub is_paused: bool,
+    pub auto_dismiss: bool,
+    pub auto_apply: bool,
+    pub sandbox: PathSandbox,
+    /// The Generator's selection for this project; `None` until the GUI has shown it.
+    pub selection: Option<ContextSelection>,
+    /// Where changes to the queue are reported; set by the owning `SharedAppState`.
+    pub(crate) events: EventSender,
+}
+
+impl Workspace {
+    /// An empty workspace with auto-apply on, as the app starts.
+    pub fn new(id: String, sandbox: PathSandbox) -> Self {
+        Self {
+            id,
+            patches: Vec::new(),
+            groups: Vec::new(),
+            new_patch_alert: false,
+            is_paused: false,
+            auto_dismiss: false,
+            auto_apply: true,
+            sandbox,
+            selection: None,
+            events: EventSender::default(),
+        }
+    }
+}
+
+/// Checks a patch against the current tree before anything in its group is written, and
+/// returns the status it should be left in when it would not apply: `Ambiguous` for a SEARCH
+/// block that matches more than once with no `target_line` or line hint to pick a match,
+/// `Failed` otherwise. Files already touched by earlier blocks of the same group are only
+/// checked for path safety, since their content depends on those blocks.
+pub fn validate_patch(
+    patch: &PatchEntry,
+    sandbox: &PathSandbox,
+    touched: &mut std::collections::HashSet<PathBuf>,
+) -> Result<(), PatchStatus> {
+    let data = &patch.data;
+    let path = sandbox
+        .resolve(&data.file_path)
+        .map_err(|e| PatchStatus::Failed(format!("Path rejected: {}", e)))?;
+    let untouched = !touched.contains(&path);
+    match data.operation {
+        PatchOperation::Replace if untouched => {
+            let raw = fs::read_to_string(&path)
+                .map_err(|e| PatchStatus::Failed(format!("File missing: {}", e)))?;
+            let content = normalized_body(&raw);
+            let search_norm = data.search_content.replace("\r\n", "\n");
+            let replace_norm = data.replace_content.replace("\r\n", "\n");
+            if !(data.line_hint.is_some() && search_norm.trim().is_empty()) {
+                match find_matches(&content, &search_norm, &replace_norm) {
+                    None => {
+                        return Err(PatchStatus::Failed(
+                            "Search text not found (Check tabs/whitespace)".to_string(),
+                        ));
+                    }
+                    Some((_, spans))
+                        if spans.len() > 1
+                            && data.line_hint.is_none()
+                            && !spans.iter().any(|s| Some(s.line) == patch.target_line) =>
+                    {
+                        return Err(PatchStatus::Ambiguous(
+                            spans.iter().map(|s| s.line).collect(),
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        PatchOperation::Create if untouched && path.exists() => {
+            return Err(PatchStatus::Failed(
+                "File already exists (use a SEARCH/REPLACE block to edit it)".to_string(),
+            ));
+        }
+        PatchOperation::Delete if untouched && !path.exists() => {
+            return Err(PatchStatus::Failed(format!(
+                "File missing: {}",
+                data.file_path
+            )));
+        }
+        PatchOperation::Rename => {
+            let target = data.new_path.clone().unwrap_or_default();
+            let new_path = sandbox
+                .resolve(&target)
+                .map_err(|e| PatchStatus::Failed(format!("Path rejected: {}", e)))?;
+            if untouched && !path.exists() {
+                return Err(PatchStatus::Failed(format!(
+                    "File missing: {}",
+                    data.file_path
+                )));
+            }
+            if !touched.contains(&new_path) && new_path.exists() {
+                return Err(PatchStatus::Failed(format!(
+                    "Rename target '{}' already exists",
+                    target
+                )));
+            }
+            touched.insert(new_path);
+        }
+        _ => {}
+    }
+    touched.insert(path);
+    Ok(())
+}
+
+/// Applies every not-yet-applied block of a response group, or none of them. All blocks are
+/// validated first; if one still fails while applying, the blocks applied before it are undone.
+/// An ambiguous block is left `Ambiguous` and the group pending until a match is picked.
+pub fn apply_group(workspace: &mut Workspace, group_id: &str) {
+    track_changes(workspace, |workspace| {
+        apply_group_untracked(workspace, group_id, true)
+    });
+}
+
+/// Without `allow_fuzzy` (auto-apply), a block that only matches fuzzily stops the group
+/// like a failing one, so nothing is written before the user has reviewed it.
+fn apply_group_untracked(workspace: &mut Workspace, group_id: &str, allow_fuzzy: bool) {
+    let indices: Vec<usize> = workspace
+        .patches
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| {
+            p.group_id.as_deref() == Some(group_id) && p.status != PatchStatus::Success
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let total = indices.len();
+
+    let mut touched = std::collections::HashSet::new();
+    for (n, &i) in indices.iter().enumerate() {
+        let patch = &mut workspace.patches[i];
+        if let Err(status) = validate_patch(patch, &workspace.sandbox, &mut touched) {
+            let reason = match &status {
+                PatchStatus::Ambiguous(lines) => {
+                    format!("{}; pick one of the matches", ambiguous_reason(lines))
+                }
+                PatchStatus::Failed(e) => e.clone(),
+                other => format!("{:?}", other),
+            };
+            patch.status = status;
+            set_group_error(
+                workspace,
+                group_id,
+                Some(format!(
+                    "Block {} of {} rejected, nothing was applied: {}",
+                    n + 1,
+                    total,
+                    reason
+                )),
+            );
+            return;
+        }
+    }
+
+    let mut applied = Vec::new();
+    for (n, &i) in indices.iter().enumerate() {
+        let patch = &mut workspace.patches[i];
+        apply_patch_with(patch, &workspace.sandbox, allow_fuzzy);
+        if patch.status == PatchStatus::Success {
+            applied.push(i);
+            continue;
+        }
+
+        let (outcome, reason) = match &patch.status {
+            PatchStatus::Failed(e) => ("failed", e.clone()),
+            PatchStatus::Ambiguous(lines) => (
+                "failed",
+                format!("Search text is ambiguous ({} matches)", lines.len()),
+            ),
+            PatchStatus::Pending => {
+                let warning = patch.warning.as_deref().unwrap_or_default();
+                ("needs review", warning.trim_end_matches('.').to_string())
+            }
+            other => ("failed", format!("{:?}", other)),
+        };
+        for &done in applied.iter().rev() {
+            undo_patch(&mut workspace.patches[done], &workspace.sandbox);
+        }
+        let stuck = applied
+            .iter()
+            .filter(|&&done| workspace.patches[done].status == PatchStatus::Success)
+            .count();
+        let mut message = format!(
+            "Block {} of {} {}: {}. Rolled back {} applied block(s).",
+            n + 1,
+            total,
+            outcome,
+            reason,
+            applied.len() - stuck
+        );
+        if stuck > 0 {
+            message.push_str(&format!(
+                " {} block(s) could not be rolled back, see below.",
+                stuck
+            ));
+        }
+        set_group_error(workspace, group_id, Some(message));
+        return;
+    }
+    set_group_error(workspace, group_id, None);
+}
+
+/// Undoes a group's applied blocks newest first, stopping at the first one that needs
+/// the user's attention.
+pub fn undo_group(workspace: &mut Workspace, group_id: &str) {
+    track_changes(workspace, |workspace| {
+        undo_group_untracked(workspace, group_id)
+    });
+}
+
+fn undo_group_untracked(workspace: &mut Workspace, group_id: &str) {
+    let indices: Vec<usize> = workspace
+        .patches
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| {
+            p.group_id.as_deref() == Some(group_id) && p.status == PatchStatus::Success
+        })
+        .map(|(i, _)| i)
+        .rev()
+        .collect();
+    for i in indices {
+        undo_patch(&mut workspace.patches[i], &workspace.sandbox);
+        if workspace.patches[i].status != PatchStatus::Pending {
+            let message = format!(
+                "Undo stopped at {}: the file changed since the patch was applied.",
+                workspace.patches[i].data.file_path
+            );
+            set_group_error(workspace, group_id, Some(message));
+            return;
+        }
+    }
+    set_group_error(workspace, group_id, None);
+}
+
+fn set_group_error(workspace: &mut Workspace, group_id: &str, error: Option<String>) {
+    if let Some(group) = workspace.groups.iter_mut().find(|g| g.id == group_id) {
+        group.error = error;
+    }
+}
+
+/// Takes a single patch from the server: dropped, queued or applied, depending on the switches.
+pub fn receive_patch(workspace: &mut Workspace, payload: IncomingPatch) -> PatchReceipt {
+    if workspace.auto_dismiss {
+        let receipt = PatchReceipt::dismissed(&payload);
+        report_dismissed_on_arrival(workspace, std::slice::from_ref(&receipt));
+        return receipt;
+    }
+    track_changes(workspace, |workspace| {
+        receive_patch_untracked(workspace, payload)
+    })
+}
+
+fn receive_patch_untracked(workspace: &mut Workspace, payload: IncomingPatch) -> PatchReceipt {
+    let mut entry = PatchEntry::new(payload);
+
+    if workspace.is_paused {
+        entry.status = PatchStatus::Queued;
+    } else if workspace.auto_apply {
+        apply_patch_with(&mut entry, &workspace.sandbox, false);
+    } else {
+        entry.status = PatchStatus::Pending;
+    }
+
+    let receipt = PatchReceipt::of(&entry);
+    workspace.patches.push(entry);
+    workspace.new_patch_alert = true;
+    receipt
+}
+
+/// Queues all blocks of one AI reply as a response group that is applied as a unit.
+/// Returns one receipt per block, in order.
+pub fn receive_group(workspace: &mut Workspace, payloads: Vec<IncomingPatch>) -> Vec<PatchReceipt> {
+    if workspace.auto_dismiss {
+        let receipts: Vec<PatchReceipt> = payloads.iter().map(PatchReceipt::dismissed).collect();
+        report_dismissed_on_arrival(workspace, &receipts);
+        return receipts;
+    }
+    track_changes(workspace, |workspace| {
+        receive_group_untracked(workspace, payloads)
+    })
+}
+
+fn receive_group_untracked(
+    workspace: &mut Workspace,
+    payloads: Vec<IncomingPatch>,
+) -> Vec<PatchReceipt> {
+    let Some(group_id) = push_group(workspace, payloads) else {
+        return Vec::new();
+    };
+    if !workspace.is_paused && workspace.auto_apply {
+        apply_group_untracked(workspace, &group_id, false);
+    }
+    workspace.new_patch_alert = true;
+    // Blocks left pending because another block failed carry the group's error.
+    let error = workspace
+        .groups
+        .iter()
+        .find(|g| g.id == group_id)
+        .and_then(|g| g.error.clone());
+    workspace
+        .patches
+        .iter()
+        .filter(|p| p.group_id.as_ref() == Some(&group_id))
+        .map(|patch| {
+            let mut receipt = PatchReceipt::of(patch);
+            if receipt.reason.is_none() {
+                receipt.reason = error.clone();
+            }
+            receipt
+        })
+        .collect()
+}
+
+/// Where a received patch ended up, as reported back to whoever sent it.
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptStatus {
+    Queued,
+    Pending,
+    Success,
+    /// Failed to apply, including ambiguous SEARCH blocks.
+    Failed,
+    /// Dropped on arrival because auto-dismiss is on; not kept in the patch list.
+    Dismissed,
+}
+
+/// Outcome of one received patch.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct PatchReceipt {
+    /// Id of the patch list entry; `None` for dismissed patches.
+    pub id: Option<String>,
+    pub file_path: String,
+    pub status: ReceiptStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl PatchReceipt {
+    pub fn of(patch: &PatchEntry) -> Self {
+        let (status, reason) = match &patch.status {
+            PatchStatus::Queued => (ReceiptStatus::Queued, None),
+            PatchStatus::Pending => (ReceiptStatus::Pending, None),
+            PatchStatus::Success => (ReceiptStatus::Success, None),
+            PatchStatus::Failed(e) => (ReceiptStatus::Failed, Some(e.clone())),
+            PatchStatus::Ambiguous(lines) => (ReceiptStatus::Failed, Some(ambiguous_reason(lines))),
+        };
+        Self {
+            id: Some(patch.id.clone()),
+            file_path: patch.data.file_path.clone(),
+            status,
+            reason,
+            warning: patch.warning.clone(),
+        }
+    }
+
+    fn dismissed(payload: &IncomingPatch) -> Self {
+        Self {
+            id: None,
+            file_path: payload.file_path.clone(),
+            status: ReceiptStatus::Dismissed,
+            reason: Some("auto-dismiss is on".to_string()),
+            warning: None,
+        }
+    }
+}
+
+/// One line per receipt for logs, in the style of [`patch_summary`], e.g.
+/// `src/lib.rs: applied` or `src/lib.rs: FAILED: SEARCH block not found`.
+impl std::fmt::Display for PatchReceipt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            ReceiptStatus::Queued => "queued",
+            ReceiptStatus::Pending => "not applied",
+            ReceiptStatus::Success => "applied",
+            ReceiptStatus::Failed => "FAILED",
+            ReceiptStatus::Dismissed => "auto-dismissed",
+        };
+        write!(f, "{}: {}", self.file_path, status)?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        if let Some(warning) = &self.warning {
+            write!(f, " ({})", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the patches as a new response group (queued while paused) and returns its id.
+pub fn push_group(workspace: &mut Workspace, payloads: Vec<IncomingPatch>) -> Option<String> {
+    track_changes(workspace, |workspace| {
+        push_group_untracked(workspace, payloads)
+    })
+}
+
+fn push_group_untracked(workspace: &mut Workspace, payloads: Vec<IncomingPatch>) -> Option<String> {
+    let entries: Vec<PatchEntry> = payloads.into_iter().map(PatchEntry::new).collect();
+    let group_id = entries.first().map(|e| format!("group-{}", e.id))?;
+    workspace.groups.push(ResponseGroup {
+        id: group_id.clone(),
+        timestamp: chrono::Local::now().format("%H:%M:%S").to_string(),
+        error: None,
+    });
+    for mut entry in entries {
+        entry.group_id = Some(group_id.clone());
+        if workspace.is_paused {
+            entry.status = PatchStatus::Queued;
+        }
+        workspace.patches.push(entry);
+    }
+    Some(group_id)
+}
+
+/// Replaces the patch list with `patches` (usually from the journal) and rebuilds the
+/// response groups their blocks belong to.
+pub fn restore_patches(workspace: &mut Workspace, patches: Vec<PatchEntry>) {
+    workspace.groups.clear();
+    for patch in &patches {
+        let Some(group_id) = &patch.group_id else {
+            continue;
+        };
+        if !workspace.groups.iter().any(|g| &g.id == group_id) {
+            workspace.groups.push(ResponseGroup {
+                id: group_id.clone(),
+                timestamp: patch.timestamp.clone(),
+                error: None,
+            });
+        }
+    }
+    workspace.patches = patches;
+}
+
+/// One-line outcome of a patch for logs and the CLI, e.g. `src/lib.rs: applied`.
+pub fn patch_summary(patch: &PatchEntry) -> String {
+    let target = match &patch.data.new_path {
+        Some(new_path) if patch.data.operation == PatchOperation::Rename => {
+            format!("{} -> {}", patch.data.file_path, new_path)
+        }
+        _ => patch.data.file_path.clone(),
+    };
+    let status = match &patch.status {
+        PatchStatus::Success => "applied".to_string(),
+        PatchStatus::Pending | PatchStatus::Queued => "not applied".to_string(),
+        PatchStatus::Failed(e) => format!("FAILED: {}", e),
+        PatchStatus::Ambiguous(lines) => format!("FAILED: {}", ambiguous_reason(lines)),
+    };
+    match &patch.warning {
+        Some(warning) => format!("{}: {} ({})", target, status, warning),
+        None => format!("{}: {}", target, status),
+    }
+}
+
+/// A Patcher-tab button pressed from outside the GUI, on a single patch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PatchAction {
+    /// Apply, retry or apply a queued patch now. `line` picks the match of an ambiguous
+    /// SEARCH block.
+    Apply { line: Option<usize> },
+    /// Undo an applied patch. `force` overwrites edits made since, after a first undo
+    /// reported them.
+    Undo { force: bool },
+    /// Remove the patch from the list; the journal keeps it.
+    Dismiss,
+}
+
+/// Runs `action` on `workspace.patches[index]` and reports the result. Fails, without
+/// changing anything, when the action does not fit the patch's status; an undo that finds
+/// later edits records the conflict and fails too. A block of a response group takes its
+/// whole group along, so the group stays all-or-nothing: apply and undo run on the group,
+/// dismiss removes the group, and failures name the group. Applied patches cannot be
+/// dismissed; undo them first.
+pub fn act_on_patch(
+    workspace: &mut Workspace,
+    index: usize,
+    action: PatchAction,
+) -> Result<PatchReceipt, String> {
+    track_changes(workspace, |workspace| {
+        act_on_patch_untracked(workspace, index, action)
+    })
+}
+
+fn act_on_patch_untracked(
+    workspace: &mut Workspace,
+    index: usize,
+    action: PatchAction,
+) -> Result<PatchReceipt, String> {
+    let sandbox = &workspace.sandbox;
+    let patch = &mut workspace.patches[index];
+    let group_id = patch.group_id.clone();
+    match action {
+        PatchAction::Apply { line } => {
+            match (&patch.status, line) {
+                (PatchStatus::Success, _) => return Err("Already applied".to_string()),
+                (PatchStatus::Ambiguous(lines), None) => {
+                    return Err(format!(
+                        "{}; pass one of them as the line",
+                        ambiguous_reason(lines)
+                    ));
+                }
+                (_, Some(_)) => patch.target_line = line,
+                _ => {}
+            }
+            match &group_id {
+                Some(group_id) => apply_group(workspace, group_id),
+                None => apply_patch(patch, sandbox),
+            }
+        }
+        PatchAction::Undo { .. } if patch.status != PatchStatus::Success => {
+            return Err("Not applied".to_string());
+        }
+        PatchAction::Undo { force: true } if patch.undo_conflict.is_some() => {
+            force_undo_patch(patch, sandbox);
+            if patch.status == PatchStatus::Pending {
+                reset_later_patches(&mut workspace.patches, index, &workspace.sandbox);
+                // The rest of the group follows, as with a plain group undo.
+                if let Some(group_id) = &group_id {
+                    undo_group(workspace, group_id);
+                }
+            }
+        }
+        PatchAction::Undo { .. } => match &group_id {
+            Some(group_id) => undo_group(workspace, group_id),
+            None => {
+                undo_patch(patch, sandbox);
+                if patch.undo_conflict.is_some() {
+                    return Err(format!(
+                        "{} was edited after the patch was applied; undo with force to overwrite the edits",
+                        patch.data.file_path
+                    ));
+                }
+            }
+        },
+        PatchAction::Dismiss => {
+            let scope: Vec<usize> = match &group_id {
+                Some(group_id) => (0..workspace.patches.len())
+                    .filter(|&i| workspace.patches[i].group_id.as_ref() == Some(group_id))
+                    .collect(),
+                None => vec![index],
+            };
+            if scope
+                .iter()
+                .any(|&i| workspace.patches[i].status == PatchStatus::Success)
+            {
+                return Err(match &group_id {
+                    Some(group_id) => format!(
+                        "Response group {} has applied blocks; undo it before dismissing",
+                        group_id
+                    ),
+                    None => "Applied; undo it before dismissing".to_string(),
+                });
+            }
+            let receipt = PatchReceipt {
+                status: ReceiptStatus::Dismissed,
+                reason: None,
+                ..PatchReceipt::of(&workspace.patches[index])
+            };
+            for &i in scope.iter().rev() {
+                journal_dismissed(&workspace.sandbox.root, &workspace.patches.remove(i));
+            }
+            if let Some(group_id) = &group_id {
+                workspace.groups.retain(|g| &g.id != group_id);
+            }
+            return Ok(receipt);
+        }
+    }
+
+    // Group actions report their outcome on the group; a stop there fails the request.
+    let group_error = group_id.as_ref().and_then(|group_id| {
+        let group = workspace.groups.iter().find(|g| &g.id == group_id)?;
+        group
+            .error
+            .as_ref()
+            .map(|e| format!("Response group {}: {}", group_id, e))
+    });
+    match group_error {
+        Some(error) => Err(error),
+        None => Ok(PatchReceipt::of(&workspace.patches[index])),
+    }
+}
+
+fn ambiguous_reason(lines: &[usize]) -> String {
+    let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    format!("search text is ambiguous (lines {})", lines.join(", "))
+}
+
+/// Unpauses the queue: queued patches become pending and, with auto-apply on, are applied,
+/// whole response groups at a time.
+pub fn resume_queue(workspace: &mut Workspace) {
+    track_changes(workspace, resume_queue_untracked);
+}
+
+fn resume_queue_untracked(workspace: &mut Workspace) {
+    workspace.is_paused = false;
+    let auto_apply = workspace.auto_apply;
+
+    let mut queued_groups: Vec<String> = Vec::new();
+    for patch in workspace.patches.iter_mut() {
+        if let PatchStatus::Queued = patch.status {
+            patch.status = PatchStatus::Pending;
+            match &patch.group_id {
+                Some(group_id) if !queued_groups.contains(group_id) => {
+                    queued_groups.push(group_id.clone())
+                }
+                Some(_) => {}
+                None if auto_apply => apply_patch_with(patch, &workspace.sandbox, false),
+                None => {}
+            }
+        }
+    }
+    if auto_apply {
+        for group_id in queued_groups {
+            apply_group_untracked(workspace, &group_id, false);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Append-only on-disk history of applied patches, so undo survives restarts.
+
+use crate::patch::{IncomingPatch, PatchEntry, PatchOperation, PatchStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Journal location, relative to the project root.
+pub const JOURNAL_DIR: &str = ".betterpaste/journal";
+
+/// One line of the on-disk patch journal. Files are append-only, one per day.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "event")]
+enum JournalRecord {
+    Applied {
+        id: String,
+        timestamp: String,
+        file_path: String,
+        search_content: String,
+        replace_content: String,
+        #[serde(default)]
+        operation: PatchOperation,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        new_path: Option<String>,
+        /// The response group the patch arrived in, so groups survive restarts.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group_id: Option<String>,
+        pre_hash: String,
+        post_hash: String,
+        backup: String,
+    },
+    Undone {
+        id: String,
+        timestamp: String,
+        /// File content that a forced undo overwrote, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        overwritten: Option<String>,
+    },
+    Dismissed {
+        id: String,
+        timestamp: String,
+    },
+}
+
+pub(crate) fn content_hash(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn append_journal(root: &Path, record: &JournalRecord) {
+    use std::io::Write;
+    let dir = root.join(JOURNAL_DIR);
+    let file = dir.join(format!("{}.jsonl", chrono::Local::now().format("%Y-%m-%d")));
+    let result = fs::create_dir_all(&dir).and_then(|_| {
+        let line = serde_json::to_string(record).map_err(std::io::Error::other)?;
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file)?;
+        writeln!(f, "{}", line)
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to write journal {}: {}", file.display(), e);
+    }
+}
+
+pub(crate) fn journal_applied(root: &Path, patch: &PatchEntry, pre_image: &str, post_image: &str) {
+    append_journal(
+        root,
+        &JournalRecord::Applied {
+            id: patch.id.clone(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            file_path: patch.data.file_path.clone(),
+            search_content: patch.data.search_content.clone(),
+            replace_content: patch.data.replace_content.clone(),
+            operation: patch.data.operation,
+            new_path: patch.data.new_path.clone(),
+            group_id: patch.group_id.clone(),
+            pre_hash: content_hash(pre_image),
+            post_hash: content_hash(post_image),
+            backup: pre_image.to_string(),
+        },
+    );
+}
+
+pub(crate) fn journal_undone(root: &Path, patch: &PatchEntry, overwritten: Option<String>) {
+    append_journal(
+        root,
+        &JournalRecord::Undone {
+            id: patch.id.clone(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            overwritten,
+        },
+    );
+}
+
+/// Records that the user removed `patch` from the list, so it is not restored on restart.
+pub fn journal_dismissed(root: &Path, patch: &PatchEntry) {
+    append_journal(
+        root,
+        &JournalRecord::Dismissed {
+            id: patch.id.clone(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+        },
+    );
+}
+
+/// Journal files sorted oldest first. Names are `YYYY-MM-DD.jsonl`, so name order is date order.
+fn journal_files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(root.join(JOURNAL_DIR))
+        .map(|rd| {
+            rd.filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Deletes whole day files older than `max_age_days`, then the oldest ones until the
+/// journal fits in `max_size_mb`.
+pub fn prune_journal(root: &Path, max_age_days: u32, max_size_mb: u64) {
+    let cutoff = (chrono::Local::now() - chrono::Duration::days(max_age_days as i64))
+        .format("%Y-%m-%d")
+        .to_string();
+    let mut kept = Vec::new();
+    for file in journal_files(root) {
+        let stem = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        if stem < cutoff.as_str() {
+            let _ = fs::remove_file(&file);
+        } else {
+            kept.push(file);
+        }
+    }
+
+    let size_of = |p: &PathBuf| fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+    let mut total: u64 = kept.iter().map(size_of).sum();
+    let max_bytes = max_size_mb * 1024 * 1024;
+    for file in &kept {
+        if total <= max_bytes {
+            break;
+        }
+        total -= size_of(file);
+        let _ = fs::remove_file(file);
+    }
+}
+
+/// Rebuilds the patch history from the journal so earlier patches can still be undone.
+pub fn load_journal(root: &Path) -> Vec<PatchEntry> {
+    let mut patches: Vec<PatchEntry> = Vec::new();
+    for file in journal_files(root) {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let record: JournalRecord = match serde_json::from_str(line) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("Skipping bad journal line in {}: {}", file.display(), e);
+                    continue;
+                }
+            };
+            match record {
+                JournalRecord::Applied {
+                    id,
+                    timestamp,
+                    file_path,
+                    search_content,
+                    replace_content,
+                    operation,
+                    new_path,
+                    group_id,
+                    post_hash,
+                    backup,
+                    ..
+                } => {
+                    patches.retain(|p| p.id != id);
+                    let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp)
+                        .map(|t| {
+                            t.with_timezone(&chrono::Local)
+                                .format("%m-%d %H:%M:%S")
+                                .to_string()
+                        })
+                        .unwrap_or(timestamp);
+                    patches.push(PatchEntry {
+                        id,
+                        timestamp,
+                        data: IncomingPatch {
+                            file_path,
+                            search_content,
+                            replace_content,
+                            operation,
+                            new_path,
+                            line_hint: None,
+                        },
+                        status: PatchStatus::Success,
+                        backup_content: Some(backup),
+                        post_hash: Some(post_hash),
+                        undo_conflict: None,
+                        match_info: None,
+                        warning: None,
+                        target_line: None,
+                        group_id,
+                    });
+                }
+                JournalRecord::Undone { id, .. } => {
+                    if let Some(p) = patches.iter_mut().find(|p| p.id == id) {
+                        p.status = PatchStatus::Pending;
+                        p.backup_content = None;
+                        p.post_hash = None;
+                    }
+                }
+                JournalRecord::Dismissed { id, .. } => patches.retain(|p| p.id != id),
+            }
+        }
+    }
+    patches
+}
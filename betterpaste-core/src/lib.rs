@@ -0,0 +1,68 @@
+//! Core of BetterPaste: project scanning with binary and generated-file detection, XML
+//! context generation, selection presets, patch parsing, patch application with undo, the
+//! on-disk patch journal, routing between open projects, patch lifecycle events, and token
+//! estimates for context budgets.
+//!
+//! The GUI, the local HTTP server and the CLI are thin layers over this crate. A typical
+//! headless round trip:
+//!
+//! ```no_run
+//! use betterpaste_core::{AppConfig, PatchEntry, PathSandbox, apply_patch, parse_patch_blocks};
+//!
+//! let config = AppConfig::default();
+//! let sandbox = PathSandbox::new(".".into(), &config);
+//! for data in parse_patch_blocks(&std::fs::read_to_string("response.md").unwrap()) {
+//!     let mut patch = PatchEntry::new(data);
+//!     apply_patch(&mut patch, &sandbox);
+//!     println!("{}", betterpaste_core::patch_summary(&patch));
+//! }
+//! ```
+
+#![allow(clippy::collapsible_if)]
+
+pub mod apply;
+pub mod atomic;
+pub mod compress;
+pub mod config;
+pub mod context;
+pub mod detect;
+pub mod events;
+pub mod filter;
+pub mod history;
+pub mod journal;
+pub mod matching;
+pub mod parse;
+pub mod patch;
+pub mod presets;
+pub mod sandbox;
+mod text;
+pub mod tokens;
+pub mod workspace;
+
+pub use apply::{apply_patch, force_undo_patch, reset_later_patches, undo_patch};
+pub use atomic::atomic_write;
+pub use compress::{
+    BODY_PLACEHOLDER, Language, Symbol, SymbolKind, SymbolMode, SymbolModes, compress_code,
+    outline_symbols, render_file,
+};
+pub use config::{API_TOKEN_HEADER, AppConfig, MAX_RECENT_PROJECTS, ModelBudget};
+pub use context::{
+    BuiltContext, ContextSelection, ExtractOutcome, build_context, extract_files, generate_xml,
+    scan_files,
+};
+pub use detect::{SkipReason, detect_skips};
+pub use events::{EventKind, QueueEvent, track_changes};
+pub use filter::FileFilter;
+pub use history::{
+    PatchAction, PatchReceipt, ReceiptStatus, ResponseGroup, Workspace, act_on_patch, apply_group,
+    patch_summary, push_group, receive_group, receive_patch, restore_patches, resume_queue,
+    undo_group, validate_patch,
+};
+pub use journal::{journal_dismissed, load_journal, prune_journal};
+pub use matching::{MatchInfo, MatchTier};
+pub use parse::{parse_patch_blocks, parse_unified_diff};
+pub use patch::{IncomingPatch, PatchEntry, PatchOperation, PatchStatus};
+pub use presets::{PRESETS_FILE, Preset, delete_preset, load_presets, save_preset};
+pub use sandbox::{PathSandbox, build_globset};
+pub use tokens::{FileTokens, context_tokens, count_file_tokens, estimate_tokens, fit_to_budget};
+pub use workspace::SharedAppState;
@@ -0,0 +1,297 @@
+//! Locates a SEARCH block in a file, from exact matches down to fuzzy ones.
+
+use serde::{Deserialize, Serialize};
+
+/// How the SEARCH block was located in the file, from strictest to loosest.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum MatchTier {
+    Exact,
+    TrailingWhitespace,
+    Indentation,
+    Fuzzy,
+}
+
+impl MatchTier {
+    /// Short name shown next to patches that did not match exactly.
+    pub fn label(&self) -> &'static str {
+        match self {
+            MatchTier::Exact => "exact",
+            MatchTier::TrailingWhitespace => "trailing ws",
+            MatchTier::Indentation => "re-indented",
+            MatchTier::Fuzzy => "fuzzy",
+        }
+    }
+}
+
+/// The tier a patch matched at, and its similarity score (1.0 unless fuzzy).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MatchInfo {
+    pub tier: MatchTier,
+    pub score: f64,
+}
+
+/// Minimum average line similarity for the fuzzy tier to accept a match.
+pub const FUZZY_MATCH_THRESHOLD: f64 = 0.85;
+
+/// Most character pairs the fuzzy tier compares before giving up on a SEARCH block, which
+/// bounds how long a block that is not in the file can take (about 0.1 s in a release build).
+const FUZZY_WORK_LIMIT: usize = 20_000_000;
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// How often each character occurs in a line; non-ASCII characters share one slot.
+fn char_counts(line: &str) -> [u16; 128] {
+    let mut counts = [0u16; 128];
+    for c in line.chars() {
+        let slot = if c.is_ascii() { c as usize } else { 0 };
+        counts[slot] = counts[slot].saturating_add(1);
+    }
+    counts
+}
+
+/// Upper bound of [`line_similarity`] from character counts alone: each character one line
+/// has more of than the other takes at least one edit.
+fn similarity_ceiling(a: &[u16; 128], a_len: usize, b: &[u16; 128], b_len: usize) -> f64 {
+    let max_len = a_len.max(b_len);
+    if max_len == 0 {
+        return 1.0;
+    }
+    let (mut extra_a, mut extra_b) = (0, 0);
+    for (x, y) in a.iter().zip(b) {
+        if x > y {
+            extra_a += (x - y) as usize;
+        } else {
+            extra_b += (y - x) as usize;
+        }
+    }
+    1.0 - extra_a.max(extra_b) as f64 / max_len as f64
+}
+
+/// Normalized Levenshtein similarity between two lines (1.0 = identical).
+fn line_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    1.0 - prev[b.len()] as f64 / max_len as f64
+}
+
+/// Shifts the indentation of `replace` so it lines up with the matched file lines.
+/// The indent delta is taken from the first non-blank line after the first one,
+/// because the userscript trims the leading indentation of the SEARCH block.
+fn reindent(replace: &[&str], search: &[&str], matched: &[&str]) -> Vec<String> {
+    let pairs: Vec<(&str, &str)> = search
+        .iter()
+        .zip(matched)
+        .map(|(s, f)| (*s, *f))
+        .filter(|(s, f)| !s.trim().is_empty() && !f.trim().is_empty())
+        .collect();
+    let Some(&(first_search, first_file)) = pairs.first() else {
+        return replace.iter().map(|l| l.to_string()).collect();
+    };
+    let (ref_search, ref_file) = pairs.get(1).copied().unwrap_or((first_search, first_file));
+    let search_indent = leading_whitespace(ref_search);
+    let file_indent = leading_whitespace(ref_file);
+    let first_line_trimmed = leading_whitespace(first_search).is_empty();
+
+    replace
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if line.trim().is_empty() {
+                String::new()
+            } else if i == 0 && first_line_trimmed && leading_whitespace(line).is_empty() {
+                format!("{}{}", leading_whitespace(first_file), line)
+            } else if let Some(extra) = file_indent.strip_suffix(search_indent) {
+                format!("{}{}", extra, line)
+            } else if let Some(extra) = search_indent.strip_suffix(file_indent) {
+                let indent = leading_whitespace(line);
+                let cut = extra.len().min(indent.len());
+                line[cut..].to_string()
+            } else if let Some(rest) = line.strip_prefix(search_indent) {
+                format!("{}{}", file_indent, rest)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+/// A candidate location of the SEARCH block, as a byte range into the normalized content.
+#[derive(Clone, Debug)]
+pub(crate) struct MatchSpan {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    /// 1-based line number where the match begins.
+    pub(crate) line: usize,
+    pub(crate) replacement: String,
+}
+
+/// Finds every location of `search` using the strictest tier that matches at all.
+pub(crate) fn find_matches(
+    content: &str,
+    search: &str,
+    replace: &str,
+) -> Option<(MatchInfo, Vec<MatchSpan>)> {
+    let exact: Vec<MatchSpan> = content
+        .match_indices(search)
+        .map(|(start, _)| MatchSpan {
+            start,
+            end: start + search.len(),
+            line: content[..start].matches('\n').count() + 1,
+            replacement: replace.to_string(),
+        })
+        .collect();
+    if !search.is_empty() && !exact.is_empty() {
+        let info = MatchInfo {
+            tier: MatchTier::Exact,
+            score: 1.0,
+        };
+        return Some((info, exact));
+    }
+    find_line_matches(content, search, replace)
+}
+
+/// Line-based fallback tiers used when the SEARCH text is not an exact substring.
+fn find_line_matches(
+    content: &str,
+    search: &str,
+    replace: &str,
+) -> Option<(MatchInfo, Vec<MatchSpan>)> {
+    let file_lines: Vec<&str> = content.split('\n').collect();
+    let search_lines: Vec<&str> = {
+        let all: Vec<&str> = search.split('\n').collect();
+        let start = all.iter().position(|l| !l.trim().is_empty())?;
+        let end = all.iter().rposition(|l| !l.trim().is_empty())?;
+        all[start..=end].to_vec()
+    };
+    let replace_lines: Vec<&str> = if replace.trim().is_empty() {
+        Vec::new()
+    } else {
+        replace.trim_matches('\n').split('\n').collect()
+    };
+    let n = search_lines.len();
+    if n > file_lines.len() {
+        return None;
+    }
+    let windows = 0..=file_lines.len() - n;
+
+    let found: Vec<usize> = windows
+        .clone()
+        .filter(|&s| (0..n).all(|k| file_lines[s + k].trim_end() == search_lines[k].trim_end()))
+        .collect();
+    let (tier, score, starts) = if !found.is_empty() {
+        (MatchTier::TrailingWhitespace, 1.0, found)
+    } else {
+        let found: Vec<usize> = windows
+            .clone()
+            .filter(|&s| (0..n).all(|k| file_lines[s + k].trim() == search_lines[k].trim()))
+            .collect();
+        if !found.is_empty() {
+            (MatchTier::Indentation, 1.0, found)
+        } else {
+            let (starts, score) = find_fuzzy(&file_lines, &search_lines)?;
+            (MatchTier::Fuzzy, score, starts)
+        }
+    };
+
+    let line_offsets: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let spans = starts
+        .into_iter()
+        .map(|s| {
+            let matched = &file_lines[s..s + n];
+            let new_lines = match tier {
+                MatchTier::TrailingWhitespace => {
+                    replace_lines.iter().map(|l| l.to_string()).collect()
+                }
+                _ => reindent(&replace_lines, &search_lines, matched),
+            };
+            let start = line_offsets[s];
+            let mut end = line_offsets[s + n - 1] + file_lines[s + n - 1].len();
+            // Dropping the block entirely should not leave an empty line behind.
+            if new_lines.is_empty() && end < content.len() {
+                end += 1;
+            }
+            MatchSpan {
+                start,
+                end,
+                line: s + 1,
+                replacement: new_lines.join("\n"),
+            }
+        })
+        .collect();
+    Some((MatchInfo { tier, score }, spans))
+}
+
+/// The fuzzy tier: the windows of `file_lines` with the best average line similarity to
+/// `search_lines`, at least [`FUZZY_MATCH_THRESHOLD`]. Every window that ties for the best
+/// score is kept so duplicates are reported. Character counts rule most windows out before
+/// any edit distance is computed, and the search gives up after [`FUZZY_WORK_LIMIT`].
+fn find_fuzzy(file_lines: &[&str], search_lines: &[&str]) -> Option<(Vec<usize>, f64)> {
+    let n = search_lines.len();
+    let counted = |lines: &[&str]| -> Vec<([u16; 128], usize)> {
+        lines
+            .iter()
+            .map(|l| (char_counts(l.trim()), l.trim().chars().count()))
+            .collect()
+    };
+    let file_counts = counted(file_lines);
+    let search_counts = counted(search_lines);
+    // Whether a window whose first `k + 1` lines add up to `sum` can still make it.
+    let hopeless = |sum: f64, k: usize, best: &Option<(Vec<usize>, f64)>| {
+        let ceiling = (sum + (n - k - 1) as f64) / n as f64;
+        ceiling < FUZZY_MATCH_THRESHOLD
+            || best
+                .as_ref()
+                .is_some_and(|(_, b)| ceiling < b - f64::EPSILON)
+    };
+
+    let mut best: Option<(Vec<usize>, f64)> = None;
+    let mut work = 0;
+    'windows: for s in 0..=file_lines.len() - n {
+        let mut sum = 0.0;
+        for k in 0..n {
+            let ((a, a_len), (b, b_len)) = (&file_counts[s + k], &search_counts[k]);
+            sum += similarity_ceiling(a, *a_len, b, *b_len);
+            if hopeless(sum, k, &best) {
+                continue 'windows;
+            }
+        }
+        let mut sum = 0.0;
+        for k in 0..n {
+            work += file_counts[s + k].1 * search_counts[k].1;
+            if work > FUZZY_WORK_LIMIT {
+                return None;
+            }
+            sum += line_similarity(file_lines[s + k].trim(), search_lines[k].trim());
+            if hopeless(sum, k, &best) {
+                continue 'windows;
+            }
+        }
+        let score = sum / n as f64;
+        match &mut best {
+            Some((starts, b)) if (score - *b).abs() <= f64::EPSILON => starts.push(s),
+            _ => best = Some((vec![s], score)),
+        }
+    }
+    best
+}
@@ -0,0 +1,237 @@
+//! Finds patch blocks and unified diffs in free-form AI output.
+
+use crate::patch::{IncomingPatch, PatchOperation};
+
+/// Extracts every patch block and unified diff hunk from an AI response, in the order they appear.
+pub fn parse_patch_blocks(text: &str) -> Vec<IncomingPatch> {
+    let re = regex::Regex::new(concat!(
+        r"\[<\(x\{START\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{SEARCH\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{REPLACEWITH\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]",
+        r"|\[<\(x\{CREATE\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{CONTENT\}x\)>\][^\S\n]*\n?([\s\S]*?)[^\S\n]*\[<\(x\{END\}x\)>\]",
+        r"|\[<\(x\{DELETE\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]",
+        r"|\[<\(x\{RENAME\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{TO\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]",
+    ))
+    .unwrap();
+    fn text_of<'t>(caps: &regex::Captures<'t>, i: usize) -> &'t str {
+        caps.get(i).map_or("", |m| m.as_str())
+    }
+
+    // The rules and example that ship with every context describe the markers; a pasted
+    // context must not turn them into real patches.
+    let rules: Vec<std::ops::Range<usize>> =
+        regex::Regex::new(r"<ReplacingRules>[\s\S]*?</ReplacingRules>")
+            .unwrap()
+            .find_iter(text)
+            .map(|m| m.range())
+            .collect();
+    let outside_rules = |pos: &usize| !rules.iter().any(|r| r.contains(pos));
+
+    let mut blocks: Vec<(usize, IncomingPatch)> = re
+        .captures_iter(text)
+        .filter(|caps| caps.get(0).is_some_and(|m| outside_rules(&m.start())))
+        .map(|caps| {
+            let (file_path, search, replace, operation, new_path) = if caps.get(1).is_some() {
+                (
+                    text_of(&caps, 1),
+                    text_of(&caps, 2),
+                    text_of(&caps, 3),
+                    PatchOperation::Replace,
+                    None,
+                )
+            } else if caps.get(4).is_some() {
+                (
+                    text_of(&caps, 4),
+                    "",
+                    text_of(&caps, 5),
+                    PatchOperation::Create,
+                    None,
+                )
+            } else if caps.get(6).is_some() {
+                (text_of(&caps, 6), "", "", PatchOperation::Delete, None)
+            } else {
+                let to = text_of(&caps, 8).trim().to_string();
+                (text_of(&caps, 7), "", "", PatchOperation::Rename, Some(to))
+            };
+            let patch = IncomingPatch {
+                file_path: file_path.trim().to_string(),
+                search_content: search.to_string(),
+                replace_content: replace.to_string(),
+                operation,
+                new_path,
+                line_hint: None,
+            };
+            (caps.get(0).map_or(0, |m| m.start()), patch)
+        })
+        .collect();
+
+    // Unified diffs are picked up as well, unless they sit inside one of the blocks above.
+    let covered: Vec<std::ops::Range<usize>> = re.find_iter(text).map(|m| m.range()).collect();
+    blocks.extend(
+        parse_unified_diff(text)
+            .into_iter()
+            .filter(|(pos, _)| !covered.iter().any(|r| r.contains(pos)) && outside_rules(pos)),
+    );
+    blocks.sort_by_key(|(pos, _)| *pos);
+    blocks.into_iter().map(|(_, patch)| patch).collect()
+}
+
+/// Parses `git diff` / `diff -u` output. Each hunk becomes its own Replace patch carrying the
+/// old start line as a hint; new, deleted and renamed files become file operations. Hunk line
+/// counts are ignored because models often get them wrong. Returns the byte offset of each
+/// patch's header next to the patch so callers can keep page order.
+pub fn parse_unified_diff(text: &str) -> Vec<(usize, IncomingPatch)> {
+    let mut lines: Vec<(usize, &str)> = Vec::new();
+    let mut offset = 0;
+    for line in text.split('\n') {
+        lines.push((offset, line.strip_suffix('\r').unwrap_or(line)));
+        offset += line.len() + 1;
+    }
+    let is_file_header = |i: usize| {
+        lines[i].1.starts_with("--- ")
+            && lines.get(i + 1).is_some_and(|(_, l)| l.starts_with("+++ "))
+    };
+    let is_body_line = |i: usize| {
+        let line = lines[i].1;
+        if line.starts_with("diff --git ") || line.starts_with("@@") || is_file_header(i) {
+            return false;
+        }
+        if line.is_empty() {
+            // Chat UIs drop the space of empty context lines; only keep them mid-hunk.
+            return lines[i + 1..]
+                .iter()
+                .find(|(_, l)| !l.is_empty())
+                .is_some_and(|(_, l)| l.starts_with([' ', '+', '-']) && !l.starts_with("--- "));
+        }
+        line.starts_with([' ', '+', '-', '\\'])
+    };
+    let diff_path = |raw: &str| -> Option<String> {
+        let path = raw
+            .split('\t')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches('"');
+        if path == "/dev/null" {
+            return None;
+        }
+        let path = path
+            .strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path);
+        Some(path.to_string())
+    };
+    let hunk_re = regex::Regex::new(r"^@@ -(\d+)").unwrap();
+
+    let mut patches = Vec::new();
+    let mut file_start = 0;
+    let mut rename: (Option<String>, Option<String>) = (None, None);
+    let mut i = 0;
+    while i < lines.len() {
+        let (pos, line) = lines[i];
+        if line.starts_with("diff --git ") {
+            file_start = pos;
+            rename = (None, None);
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            rename.0 = Some(from.trim().to_string());
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            rename.1 = Some(to.trim().to_string());
+        }
+        if let (Some(from), Some(to)) = (&rename.0, &rename.1) {
+            patches.push((
+                file_start,
+                IncomingPatch {
+                    file_path: from.clone(),
+                    search_content: String::new(),
+                    replace_content: String::new(),
+                    operation: PatchOperation::Rename,
+                    new_path: Some(to.clone()),
+                    line_hint: None,
+                },
+            ));
+            rename = (None, None);
+        }
+        if !is_file_header(i) {
+            i += 1;
+            continue;
+        }
+
+        let header_pos = pos;
+        let old_path = diff_path(&line[4..]);
+        let new_path = diff_path(&lines[i + 1].1[4..]);
+        i += 2;
+
+        let mut hunks: Vec<(usize, usize, Vec<&str>)> = Vec::new();
+        while i < lines.len() && lines[i].1.starts_with("@@") {
+            let (hunk_pos, header) = lines[i];
+            let old_start = hunk_re
+                .captures(header)
+                .and_then(|c| c[1].parse().ok())
+                .unwrap_or(0);
+            i += 1;
+            let body_start = i;
+            while i < lines.len() && is_body_line(i) {
+                i += 1;
+            }
+            let body = lines[body_start..i].iter().map(|(_, l)| *l).collect();
+            hunks.push((hunk_pos, old_start, body));
+        }
+
+        let side = |body: &[&str], keep: char| -> String {
+            body.iter()
+                .filter_map(|l| match l.chars().next() {
+                    None => Some(""),
+                    Some(c) if c == ' ' || c == keep => Some(&l[1..]),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        match (old_path, new_path) {
+            (None, Some(path)) => {
+                let body: Vec<&str> = hunks
+                    .iter()
+                    .flat_map(|(_, _, b)| b.iter().copied())
+                    .collect();
+                patches.push((
+                    header_pos,
+                    IncomingPatch {
+                        file_path: path,
+                        search_content: String::new(),
+                        replace_content: format!("{}\n", side(&body, '+')),
+                        operation: PatchOperation::Create,
+                        new_path: None,
+                        line_hint: None,
+                    },
+                ));
+            }
+            (Some(path), None) => patches.push((
+                header_pos,
+                IncomingPatch {
+                    file_path: path,
+                    search_content: String::new(),
+                    replace_content: String::new(),
+                    operation: PatchOperation::Delete,
+                    new_path: None,
+                    line_hint: None,
+                },
+            )),
+            (Some(_), Some(path)) => {
+                for (hunk_pos, old_start, body) in hunks {
+                    patches.push((
+                        hunk_pos,
+                        IncomingPatch {
+                            file_path: path.clone(),
+                            search_content: side(&body, '-'),
+                            replace_content: side(&body, '+'),
+                            operation: PatchOperation::Replace,
+                            new_path: None,
+                            line_hint: Some(old_start),
+                        },
+                    ));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    patches
+}
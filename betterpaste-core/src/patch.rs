@@ -0,0 +1,126 @@
+//! Patch data as received from the AI, and its state once queued.
+
+use crate::matching::MatchInfo;
+use serde::{Deserialize, Serialize};
+
+/// Where a patch is in its lifecycle.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum PatchStatus {
+    Queued,
+    Pending,
+    Success,
+    Failed(String),
+    /// The SEARCH block matched more than once; holds the 1-based start line of each match.
+    Ambiguous(Vec<usize>),
+}
+
+/// What a patch does to `file_path`. Plain SEARCH/REPLACE blocks are `Replace`.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOperation {
+    #[default]
+    Replace,
+    /// Creates `file_path` with `replace_content` as its full content.
+    Create,
+    Delete,
+    /// Moves `file_path` to `new_path`.
+    Rename,
+}
+
+/// A patch as sent by the userscript or parsed from a response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IncomingPatch {
+    pub file_path: String,
+    #[serde(default)]
+    pub search_content: String,
+    #[serde(default)]
+    pub replace_content: String,
+    #[serde(default)]
+    pub operation: PatchOperation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+    /// Old start line from a unified diff hunk header. Used to pick the nearest match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_hint: Option<usize>,
+}
+
+impl IncomingPatch {
+    /// Renders the patch back into the block format the AI is asked to use.
+    pub fn to_block(&self) -> String {
+        match self.operation {
+            PatchOperation::Replace => format!(
+                "[<(x{{START}}x)>]\n{}\n[<(x{{SEARCH}}x)>]\n{}\n[<(x{{REPLACEWITH}}x)>]\n{}\n[<(x{{END}}x)>]",
+                self.file_path, self.search_content, self.replace_content
+            ),
+            // The content keeps its own trailing newline, which the parser reads back as is.
+            PatchOperation::Create => format!(
+                "[<(x{{CREATE}}x)>]\n{}\n[<(x{{CONTENT}}x)>]\n{}{}[<(x{{END}}x)>]",
+                self.file_path,
+                self.replace_content,
+                if self.replace_content.is_empty() || self.replace_content.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                }
+            ),
+            PatchOperation::Delete => {
+                format!("[<(x{{DELETE}}x)>]\n{}\n[<(x{{END}}x)>]", self.file_path)
+            }
+            PatchOperation::Rename => format!(
+                "[<(x{{RENAME}}x)>]\n{}\n[<(x{{TO}}x)>]\n{}\n[<(x{{END}}x)>]",
+                self.file_path,
+                self.new_path.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// A received patch together with its status, backup and match details.
+#[derive(Clone, Debug)]
+pub struct PatchEntry {
+    pub id: String,
+    pub timestamp: String,
+    pub data: IncomingPatch,
+    pub status: PatchStatus,
+    pub backup_content: Option<String>,
+    /// Hash of the file right after this patch was applied, used to detect later edits.
+    pub post_hash: Option<String>,
+    /// Current file content when undo found later edits; set until the user confirms or cancels.
+    pub undo_conflict: Option<String>,
+    pub match_info: Option<MatchInfo>,
+    /// Non-fatal note from the last apply, e.g. mixed line endings in the target file.
+    pub warning: Option<String>,
+    /// Start line picked by the user when the SEARCH block is ambiguous.
+    pub target_line: Option<usize>,
+    /// Response group this patch was received in, if it came as part of a batch.
+    pub group_id: Option<String>,
+}
+
+impl PatchEntry {
+    /// Wraps a received patch as a new, pending list entry with a unique id.
+    pub fn new(data: IncomingPatch) -> Self {
+        // Ids are microsecond timestamps, bumped so blocks parsed together stay unique.
+        static LAST_ID: std::sync::atomic::AtomicI64 = std::sync::atomic::AtomicI64::new(0);
+        let now = chrono::Utc::now().timestamp_micros();
+        let prev = LAST_ID
+            .fetch_update(
+                std::sync::atomic::Ordering::SeqCst,
+                std::sync::atomic::Ordering::SeqCst,
+                |last| Some(now.max(last + 1)),
+            )
+            .unwrap_or(now);
+        Self {
+            id: format!("{}", now.max(prev + 1)),
+            timestamp: chrono::Local::now().format("%H:%M:%S").to_string(),
+            data,
+            status: PatchStatus::Pending,
+            backup_content: None,
+            post_hash: None,
+            undo_conflict: None,
+            match_info: None,
+            warning: None,
+            target_line: None,
+            group_id: None,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Named selection presets ("context profiles"), saved per project so the same file
+//! selection and instructions can be brought back with one click or one CLI flag.
+
+use crate::atomic::atomic_write;
+use crate::config::AppConfig;
+use crate::sandbox::build_globset;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Preset file location, relative to the project root.
+pub const PRESETS_FILE: &str = ".betterpaste/presets.json";
+
+/// A named file selection with optional overrides for the context's instructions and rules.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Preset {
+    pub name: String,
+    /// Globs (relative to the project root) of the files to select. Empty selects every file.
+    pub include_globs: Vec<String>,
+    /// Globs of files never selected, checked after `include_globs`.
+    pub exclude_globs: Vec<String>,
+    /// Globs of selected files that are sent partial.
+    pub partial_globs: Vec<String>,
+    /// Replaces the configured instructions when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// Replaces the configured replacing rules when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacing_rules: Option<String>,
+}
+
+impl Preset {
+    /// A preset that selects exactly `selected` and sends `partials` partial. Selecting every
+    /// file is stored as an empty include list, so files added later are picked up too. An
+    /// empty selection is an error, since its empty include list would select everything.
+    pub fn from_selection(
+        name: &str,
+        files: &[PathBuf],
+        selected: &HashMap<PathBuf, bool>,
+        partials: &HashMap<PathBuf, bool>,
+    ) -> Result<Self, String> {
+        let is_selected = |f: &PathBuf| *selected.get(f).unwrap_or(&false);
+        if !files.iter().any(is_selected) {
+            return Err("Select at least one file before saving a preset".to_string());
+        }
+        let as_glob = |f: &PathBuf| globset::escape(&f.to_string_lossy().replace('\\', "/"));
+        let include_globs = if files.iter().all(is_selected) {
+            Vec::new()
+        } else {
+            files
+                .iter()
+                .filter(|f| is_selected(f))
+                .map(as_glob)
+                .collect()
+        };
+        let partial_globs = files
+            .iter()
+            .filter(|f| is_selected(f) && *partials.get(*f).unwrap_or(&false))
+            .map(as_glob)
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            include_globs,
+            partial_globs,
+            ..Self::default()
+        })
+    }
+
+    /// The selected and partial flags this preset gives `files`.
+    pub fn selection(&self, files: &[PathBuf]) -> (HashMap<PathBuf, bool>, HashMap<PathBuf, bool>) {
+        let include = build_globset(&self.include_globs);
+        let exclude = build_globset(&self.exclude_globs);
+        let partial = build_globset(&self.partial_globs);
+        let selected: HashMap<PathBuf, bool> = files
+            .iter()
+            .map(|f| {
+                let is_included = self.include_globs.is_empty() || include.is_match(f);
+                (f.clone(), is_included && !exclude.is_match(f))
+            })
+            .collect();
+        let partials = files
+            .iter()
+            .map(|f| (f.clone(), selected[f] && partial.is_match(f)))
+            .collect();
+        (selected, partials)
+    }
+
+    /// `config` with this preset's instructions and rules in place of its own.
+    pub fn apply_to(&self, config: &AppConfig) -> AppConfig {
+        let mut config = config.clone();
+        if let Some(instructions) = &self.instructions {
+            config.instructions = instructions.clone();
+        }
+        if let Some(rules) = &self.replacing_rules {
+            config.replacing_rules = rules.clone();
+        }
+        config
+    }
+}
+
+/// Reads the project's presets. A project without a preset file has none; a file that
+/// cannot be parsed is an error rather than an empty list, so saving never overwrites it.
+pub fn load_presets(root: &Path) -> Result<Vec<Preset>, String> {
+    let path = root.join(PRESETS_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Adds `preset` to the project's presets, replacing the one with the same name.
+pub fn save_preset(root: &Path, preset: Preset) -> Result<(), String> {
+    let mut presets = load_presets(root)?;
+    match presets.iter_mut().find(|p| p.name == preset.name) {
+        Some(existing) => *existing = preset,
+        None => presets.push(preset),
+    }
+    write_presets(root, &presets)
+}
+
+/// Removes the preset called `name`. Returns whether there was one.
+pub fn delete_preset(root: &Path, name: &str) -> Result<bool, String> {
+    let mut presets = load_presets(root)?;
+    let count = presets.len();
+    presets.retain(|p| p.name != name);
+    if presets.len() == count {
+        return Ok(false);
+    }
+    write_presets(root, &presets).map(|_| true)
+}
+
+fn write_presets(root: &Path, presets: &[Preset]) -> Result<(), String> {
+    let path = root.join(PRESETS_FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(presets).map_err(|e| e.to_string())?;
+    atomic_write(&path, json)
+}
@@ -0,0 +1,116 @@
+//! Confinement of AI-supplied paths to the project root.
+
+use crate::config::AppConfig;
+use std::{fs, path::PathBuf};
+
+/// Confines every path coming from the AI to the project root.
+pub struct PathSandbox {
+    pub root: PathBuf,
+    allow: globset::GlobSet,
+    deny: globset::GlobSet,
+}
+
+impl PathSandbox {
+    /// Builds the sandbox for `root` from the allow and deny globs in `config`.
+    pub fn new(root: PathBuf, config: &AppConfig) -> Self {
+        Self {
+            root,
+            allow: build_globset(&config.patch_allow_globs),
+            deny: build_globset(&config.patch_deny_globs),
+        }
+    }
+
+    /// Resolves a project-relative path, rejecting anything that would land outside the root,
+    /// inside `.git/`, containing control characters, or outside the configured allow/deny lists.
+    pub fn resolve(&self, relative: &str) -> Result<PathBuf, String> {
+        use std::path::Component;
+
+        if relative.trim().chars().any(char::is_control) {
+            return Err(format!(
+                "'{}' contains a line break or control character",
+                relative.escape_debug()
+            ));
+        }
+        let mut clean = PathBuf::new();
+        for component in std::path::Path::new(relative.trim()).components() {
+            match component {
+                Component::Normal(part) => {
+                    if is_protected(part) {
+                        return Err(format!(
+                            "'{}' touches {}/",
+                            relative,
+                            part.to_string_lossy()
+                        ));
+                    }
+                    clean.push(part);
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !clean.pop() {
+                        return Err(format!("'{}' escapes the project root", relative));
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(format!("'{}' is an absolute path", relative));
+                }
+            }
+        }
+        if clean.as_os_str().is_empty() {
+            return Err("Empty file path".to_string());
+        }
+
+        if self.deny.is_match(&clean) {
+            return Err(format!("'{}' matches a deny pattern", clean.display()));
+        }
+        if !self.allow.is_empty() && !self.allow.is_match(&clean) {
+            return Err(format!("'{}' is not in the allow list", clean.display()));
+        }
+
+        let root = self
+            .root
+            .canonicalize()
+            .map_err(|e| format!("Project root unavailable: {}", e))?;
+        let full = root.join(&clean);
+
+        // Resolve symlinks on the deepest part of the path that already exists.
+        let mut existing = full.clone();
+        while fs::symlink_metadata(&existing).is_err() && existing.pop() {}
+        let real = existing
+            .canonicalize()
+            .map_err(|e| format!("'{}' cannot be resolved: {}", clean.display(), e))?;
+        match real.strip_prefix(&root) {
+            Ok(inside) if !inside.components().any(|c| is_protected(c.as_os_str())) => Ok(full),
+            Ok(_) => Err(format!(
+                "'{}' resolves into a protected folder",
+                clean.display()
+            )),
+            Err(_) => Err(format!(
+                "'{}' goes through a symlink outside the project root",
+                clean.display()
+            )),
+        }
+    }
+}
+
+/// True for `.git` and `.betterpaste` in any letter case, since case-insensitive file
+/// systems treat `.GIT` as the same folder.
+fn is_protected(part: &std::ffi::OsStr) -> bool {
+    let part = part.to_string_lossy();
+    part.eq_ignore_ascii_case(".git") || part.eq_ignore_ascii_case(".betterpaste")
+}
+
+/// Compiles glob patterns, skipping (and reporting) invalid ones.
+pub fn build_globset(patterns: &[String]) -> globset::GlobSet {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        match globset::Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => eprintln!("Ignoring invalid glob '{}': {}", pattern, e),
+        }
+    }
+    builder
+        .build()
+        .unwrap_or_else(|_| globset::GlobSet::empty())
+}
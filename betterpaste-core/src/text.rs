@@ -0,0 +1,84 @@
+//! Byte-exact editing that keeps a file's line endings, BOM and final newline.
+
+use crate::matching::MatchSpan;
+
+/// Line ending, BOM and trailing newline conventions of a file as found on disk.
+pub(crate) struct TextFormat {
+    bom: bool,
+    crlf: usize,
+    lf: usize,
+    trailing_newline: bool,
+}
+
+impl TextFormat {
+    pub(crate) fn detect(raw: &str) -> Self {
+        let crlf = raw.matches("\r\n").count();
+        Self {
+            bom: raw.starts_with('\u{feff}'),
+            crlf,
+            lf: raw.matches('\n').count() - crlf,
+            trailing_newline: raw.ends_with('\n'),
+        }
+    }
+
+    /// The dominant line ending, used for lines the patch inserts.
+    pub(crate) fn line_ending(&self) -> &'static str {
+        if self.crlf > self.lf { "\r\n" } else { "\n" }
+    }
+
+    pub(crate) fn mixed_warning(&self) -> Option<String> {
+        (self.crlf > 0 && self.lf > 0).then(|| {
+            format!(
+                "Mixed line endings ({} CRLF, {} LF). Untouched lines were kept as-is, new lines use {}.",
+                self.crlf,
+                self.lf,
+                if self.line_ending() == "\r\n" { "CRLF" } else { "LF" }
+            )
+        })
+    }
+}
+
+/// Strips the BOM and normalizes line endings so SEARCH blocks can be matched.
+pub(crate) fn normalized_body(raw: &str) -> String {
+    raw.strip_prefix('\u{feff}')
+        .unwrap_or(raw)
+        .replace("\r\n", "\n")
+}
+
+/// Maps a byte offset in the normalized body back to the raw body (BOM already stripped).
+fn raw_offset(body: &str, normalized: usize) -> usize {
+    let bytes = body.as_bytes();
+    let mut r = 0;
+    for _ in 0..normalized {
+        r += if bytes[r] == b'\r' && bytes.get(r + 1) == Some(&b'\n') {
+            2
+        } else {
+            1
+        };
+    }
+    r
+}
+
+/// Replaces `span` (found in the normalized body) inside the raw content. Everything outside
+/// the span, including a BOM, stays byte-for-byte identical; the replacement uses the file's
+/// line ending.
+pub(crate) fn splice_raw(raw: &str, span: &MatchSpan) -> String {
+    let format = TextFormat::detect(raw);
+    let body = if format.bom {
+        &raw['\u{feff}'.len_utf8()..]
+    } else {
+        raw
+    };
+    let replacement = span.replacement.replace('\n', format.line_ending());
+    let mut out = format!(
+        "{}{}{}{}",
+        if format.bom { "\u{feff}" } else { "" },
+        &body[..raw_offset(body, span.start)],
+        replacement,
+        &body[raw_offset(body, span.end)..]
+    );
+    if format.trailing_newline && !out.ends_with('\n') {
+        out.push_str(format.line_ending());
+    }
+    out
+}
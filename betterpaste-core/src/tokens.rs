@@ -0,0 +1,141 @@
+//! Offline token estimates for context budgets.
+//!
+//! The estimate mimics how BPE tokenizers such as `cl100k` split text: words merge into
+//! chunks of about four letters, a single space joins the word after it, digits group by
+//! three, and every symbol costs a token of its own. It is usually within 10-15% of the
+//! real count for source code, which is enough to stay inside a model's window.
+
+use crate::compress::{SymbolModes, render_file};
+use crate::config::AppConfig;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Estimates how many tokens a model needs for `text`.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_alphabetic() || c == '_' {
+            let mut len: usize = 1;
+            while chars
+                .next_if(|c| c.is_ascii_alphabetic() || *c == '_')
+                .is_some()
+            {
+                len += 1;
+            }
+            tokens += len.div_ceil(4);
+        } else if c.is_ascii_digit() {
+            let mut len: usize = 1;
+            while chars.next_if(char::is_ascii_digit).is_some() {
+                len += 1;
+            }
+            tokens += len.div_ceil(3);
+        } else if c.is_whitespace() {
+            let mut len: usize = 1;
+            while chars.next_if(|c| c.is_whitespace()).is_some() {
+                len += 1;
+            }
+            // A lone space is merged into the following word.
+            if !(c == ' ' && len == 1) {
+                tokens += 1;
+            }
+        } else {
+            tokens += 1;
+        }
+    }
+    tokens
+}
+
+/// Token estimates for one file, as sent in full and as sent compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FileTokens {
+    pub full: usize,
+    pub compressed: usize,
+}
+
+/// Estimates every readable file in `files`, with the symbol modes from `symbols` applied.
+/// Unreadable files are left out, just as `generate_xml` leaves them out.
+pub fn count_file_tokens(
+    root: &Path,
+    files: &[PathBuf],
+    symbols: &HashMap<PathBuf, SymbolModes>,
+) -> HashMap<PathBuf, FileTokens> {
+    let no_modes = SymbolModes::new();
+    files
+        .iter()
+        .filter_map(|file| {
+            let content = fs::read_to_string(root.join(file)).ok()?;
+            let modes = symbols.get(file).unwrap_or(&no_modes);
+            let counts = FileTokens {
+                full: estimate_tokens(&render_file(file, &content, false, modes)),
+                compressed: estimate_tokens(&render_file(file, &content, true, modes)),
+            };
+            Some((file.clone(), counts))
+        })
+        .collect()
+}
+
+/// Estimates the size of the context `generate_xml` would build for this selection.
+pub fn context_tokens(
+    files: &[PathBuf],
+    selected: &HashMap<PathBuf, bool>,
+    partials: &HashMap<PathBuf, bool>,
+    counts: &HashMap<PathBuf, FileTokens>,
+    config: &AppConfig,
+) -> usize {
+    // The fixed tags around the instructions, rules and file list.
+    const FRAME_TOKENS: usize = 40;
+    let mut total = FRAME_TOKENS
+        + estimate_tokens(&config.instructions)
+        + estimate_tokens(&config.replacing_rules)
+        + estimate_tokens(&config.example);
+    for file in files.iter().filter(|f| *selected.get(*f).unwrap_or(&false)) {
+        let Some(count) = counts.get(file) else {
+            continue;
+        };
+        let path = file.display().to_string();
+        let is_partial = *partials.get(file).unwrap_or(&false);
+        total += 2 * estimate_tokens(&path) + 14;
+        total += if is_partial {
+            count.compressed
+        } else {
+            count.full
+        };
+    }
+    total
+}
+
+/// Marks the largest selected full files as partial until the context fits in `budget`.
+/// Returns the files that were switched, largest first. If compressing everything is not
+/// enough, every selected file ends up partial and the context is still over budget.
+pub fn fit_to_budget(
+    files: &[PathBuf],
+    selected: &HashMap<PathBuf, bool>,
+    partials: &mut HashMap<PathBuf, bool>,
+    counts: &HashMap<PathBuf, FileTokens>,
+    config: &AppConfig,
+    budget: usize,
+) -> Vec<PathBuf> {
+    let mut total = context_tokens(files, selected, partials, counts, config);
+    let mut candidates: Vec<(&PathBuf, FileTokens)> = files
+        .iter()
+        .filter(|f| *selected.get(*f).unwrap_or(&false) && !*partials.get(*f).unwrap_or(&false))
+        .filter_map(|f| counts.get(f).map(|c| (f, *c)))
+        .filter(|(_, c)| c.compressed < c.full)
+        .collect();
+    candidates.sort_by_key(|(_, c)| std::cmp::Reverse(c.full));
+
+    let mut switched = Vec::new();
+    for (file, count) in candidates {
+        if total <= budget {
+            break;
+        }
+        partials.insert(file.clone(), true);
+        total -= count.full - count.compressed;
+        switched.push(file.clone());
+    }
+    switched
+}
@@ -0,0 +1,142 @@
+//! The set of open projects and routing of incoming patches between them.
+
+use crate::config::AppConfig;
+use crate::events::{EventSender, QueueEvent};
+use crate::history::Workspace;
+use crate::patch::{IncomingPatch, PatchOperation};
+use crate::sandbox::PathSandbox;
+use std::{path::PathBuf, sync::mpsc};
+
+/// Everything the server, the GUI and the CLI share: the open workspaces and which one
+/// the user is looking at.
+pub struct SharedAppState {
+    pub workspaces: Vec<Workspace>,
+    /// Index of the workspace shown in the GUI; patches that match no workspace land here.
+    pub active: usize,
+    events: EventSender,
+}
+
+impl SharedAppState {
+    /// Starts with `root` as the only, active workspace.
+    pub fn new(root: PathBuf, config: &AppConfig) -> Self {
+        let mut state = Self {
+            workspaces: Vec::new(),
+            active: 0,
+            events: EventSender::default(),
+        };
+        state.open(root, config);
+        state
+    }
+
+    pub fn active(&self) -> &Workspace {
+        &self.workspaces[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Workspace {
+        &mut self.workspaces[self.active]
+    }
+
+    /// Opens `root` as a new, empty workspace and returns its index; its journal is left for
+    /// the caller to load. A root that is already open is not opened twice.
+    pub fn open(&mut self, root: PathBuf, config: &AppConfig) -> usize {
+        if let Some(i) = self.workspaces.iter().position(|w| w.sandbox.root == root) {
+            return i;
+        }
+        let mut workspace = Workspace::new(self.unique_id(&root), PathSandbox::new(root, config));
+        workspace.events = self.events.clone();
+        self.workspaces.push(workspace);
+        self.workspaces.len() - 1
+    }
+
+    /// Starts reporting queue changes of every open workspace, and of those opened later, as
+    /// [`QueueEvent`]s on the returned channel. A new subscription replaces the previous one.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<QueueEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.events = EventSender::new(sender);
+        for workspace in &mut self.workspaces {
+            workspace.events = self.events.clone();
+        }
+        receiver
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        self.events.is_subscribed()
+    }
+
+    /// Closes the workspace at `index`. The last open workspace cannot be closed.
+    pub fn close(&mut self, index: usize) {
+        if self.workspaces.len() > 1 && index < self.workspaces.len() {
+            self.workspaces.remove(index);
+            if self.active >= index && self.active > 0 {
+                self.active -= 1;
+            }
+        }
+    }
+
+    /// Picks the workspace for a set of incoming patches. An explicit `project` must name an
+    /// open workspace. Otherwise the workspace whose tree contains the most of the patched
+    /// files wins, with ties and no match at all going to the active workspace.
+    pub fn route(&self, project: Option<&str>, patches: &[IncomingPatch]) -> Result<usize, String> {
+        if let Some(project) = project {
+            return self
+                .workspaces
+                .iter()
+                .position(|w| w.id == project)
+                .ok_or_else(|| format!("No open project '{}'", project));
+        }
+        let score = |w: &Workspace| patches.iter().filter(|p| targets_tree(w, p)).count();
+        let active_score = score(self.active());
+        let mut best = (self.active, active_score);
+        for (i, workspace) in self.workspaces.iter().enumerate() {
+            let s = score(workspace);
+            if s > best.1 {
+                best = (i, s);
+            }
+        }
+        let tied = self
+            .workspaces
+            .iter()
+            .filter(|w| score(w) == best.1)
+            .count();
+        Ok(if tied > 1 { self.active } else { best.0 })
+    }
+
+    /// Workspace and list index of the patch with this id.
+    pub fn find_patch(&self, id: &str) -> Option<(usize, usize)> {
+        self.workspaces
+            .iter()
+            .enumerate()
+            .find_map(|(w, workspace)| {
+                Some((w, workspace.patches.iter().position(|p| p.id == id)?))
+            })
+    }
+
+    /// Workspace id for `root`: its folder name, numbered if another workspace has it.
+    fn unique_id(&self, root: &std::path::Path) -> String {
+        let base = std::path::absolute(root)
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "project".to_string());
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.workspaces.iter().any(|w| w.id == id) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        id
+    }
+}
+
+/// Whether `patch` looks like it was written against `workspace`: the file exists there,
+/// or for a new file, its folder does.
+fn targets_tree(workspace: &Workspace, patch: &IncomingPatch) -> bool {
+    let Ok(path) = workspace.sandbox.resolve(&patch.file_path) else {
+        return false;
+    };
+    match patch.operation {
+        PatchOperation::Create => path
+            .parent()
+            .is_some_and(|p| p != workspace.sandbox.root && p.is_dir()),
+        _ => path.is_file(),
+    }
+}
@@ -0,0 +1,395 @@
+mod common;
+
+use betterpaste_core::{
+    MatchTier, PatchEntry, PatchOperation, PatchStatus, apply_patch, force_undo_patch,
+    parse_unified_diff, undo_patch,
+};
+use common::{Project, operation, replace};
+
+#[test]
+fn exact_replace_and_undo() {
+    let project = Project::new();
+    project.write("src/lib.rs", "fn a() {}\nfn b() {}\n");
+
+    let mut patch = replace("src/lib.rs", "fn b() {}", "fn b() -> u8 { 1 }");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(patch.match_info.as_ref().unwrap().tier, MatchTier::Exact);
+    assert_eq!(
+        project.read("src/lib.rs"),
+        "fn a() {}\nfn b() -> u8 { 1 }\n"
+    );
+
+    undo_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Pending);
+    assert_eq!(project.read("src/lib.rs"), "fn a() {}\nfn b() {}\n");
+}
+
+#[test]
+fn missing_search_text_fails_without_writing() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+
+    let mut patch = replace("a.txt", "two", "three");
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert_eq!(project.read("a.txt"), "one\n");
+}
+
+#[test]
+fn reindents_search_block_with_wrong_indentation() {
+    let project = Project::new();
+    project.write(
+        "a.rs",
+        "fn main() {\n        let x = 1;\n        let y = 2;\n}\n",
+    );
+
+    let mut patch = replace("a.rs", "let x = 1;\nlet y = 2;", "let x = 10;\nlet y = 20;");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(
+        patch.match_info.as_ref().unwrap().tier,
+        MatchTier::Indentation
+    );
+    assert_eq!(
+        project.read("a.rs"),
+        "fn main() {\n        let x = 10;\n        let y = 20;\n}\n"
+    );
+}
+
+#[test]
+fn ignores_trailing_whitespace_in_the_file() {
+    let project = Project::new();
+    project.write("a.txt", "a  \nb\t\nc\n");
+
+    let mut patch = replace("a.txt", "a\nb", "A\nB");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(
+        patch.match_info.as_ref().unwrap().tier,
+        MatchTier::TrailingWhitespace
+    );
+    assert_eq!(project.read("a.txt"), "A\nB\nc\n");
+}
+
+#[test]
+fn fuzzy_matches_stop_at_the_threshold() {
+    let project = Project::new();
+    project.write("a.rs", "fn a() {}\nlet retries = 10000;\nfn b() {}\n");
+
+    // Two edits in 20 characters: 90% similar.
+    let mut patch = replace("a.rs", "let retries = 10011;", "let retries = 3;");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    let info = patch.match_info.as_ref().unwrap();
+    assert_eq!(info.tier, MatchTier::Fuzzy);
+    assert!((info.score - 0.9).abs() < 1e-9);
+    assert_eq!(
+        project.read("a.rs"),
+        "fn a() {}\nlet retries = 3;\nfn b() {}\n"
+    );
+
+    // Four edits in 20 characters: 80%, under the 85% threshold.
+    project.write("a.rs", "fn a() {}\nlet retries = 10000;\nfn b() {}\n");
+    let mut patch = replace("a.rs", "let retries = 11111;", "let retries = 3;");
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert_eq!(
+        project.read("a.rs"),
+        "fn a() {}\nlet retries = 10000;\nfn b() {}\n"
+    );
+}
+
+#[test]
+fn ambiguous_search_waits_for_a_target_line() {
+    let project = Project::new();
+    project.write("a.txt", "x = 1\ny\nx = 1\n");
+
+    let mut patch = replace("a.txt", "x = 1", "x = 2");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Ambiguous(vec![1, 3]));
+    assert_eq!(project.read("a.txt"), "x = 1\ny\nx = 1\n");
+
+    patch.target_line = Some(3);
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(project.read("a.txt"), "x = 1\ny\nx = 2\n");
+}
+
+fn hunk(diff: &str) -> PatchEntry {
+    let mut patches = parse_unified_diff(diff);
+    assert_eq!(patches.len(), 1);
+    PatchEntry::new(patches.remove(0).1)
+}
+
+#[test]
+fn hunk_line_hints_pick_the_match_and_report_offsets() {
+    let project = Project::new();
+    project.write("a.txt", "x\ny\nx\ny\n");
+    let mut patch = hunk("--- a/a.txt\n+++ b/a.txt\n@@ -3,2 +3,2 @@\n x\n-y\n+Y\n");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(patch.warning, None);
+    assert_eq!(project.read("a.txt"), "x\ny\nx\nY\n");
+
+    project.write("a.txt", "a\nb\nx\ny\nz\n");
+    let mut patch = hunk("--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n x\n-y\n+Y\n");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(project.read("a.txt"), "a\nb\nx\nY\nz\n");
+    assert_eq!(
+        patch.warning.as_deref(),
+        Some("Hunk applied at line 3 (offset 2 lines).")
+    );
+}
+
+#[test]
+fn insertion_hunks_add_lines_at_the_start_and_end() {
+    let project = Project::new();
+    project.write("a.txt", "one\ntwo\n");
+    let mut patch = hunk("--- a/a.txt\n+++ b/a.txt\n@@ -0,0 +1 @@\n+zero\n");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(project.read("a.txt"), "zero\none\ntwo\n");
+
+    let mut patch = hunk("--- a/a.txt\n+++ b/a.txt\n@@ -3,0 +4 @@\n+three\n");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(project.read("a.txt"), "zero\none\ntwo\nthree\n");
+
+    project.write("a.txt", "one\ntwo");
+    let mut patch = hunk("--- a/a.txt\n+++ b/a.txt\n@@ -2,0 +3 @@\n+three\n");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(project.read("a.txt"), "one\ntwo\nthree");
+}
+
+#[cfg(unix)]
+#[test]
+fn writes_through_symlinks_in_the_root() {
+    let project = Project::new();
+    project.write("real.txt", "one\n");
+    std::os::unix::fs::symlink(
+        project.root().join("real.txt"),
+        project.root().join("link.txt"),
+    )
+    .unwrap();
+
+    let mut patch = replace("link.txt", "one", "1");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert!(
+        std::fs::symlink_metadata(project.root().join("link.txt"))
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(project.read("real.txt"), "1\n");
+}
+
+#[test]
+fn failed_write_leaves_nothing_to_undo() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    let path = project.root().join("a.txt");
+    let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&path, permissions).unwrap();
+
+    let mut patch = replace("a.txt", "one", "1");
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert_eq!(patch.backup_content, None);
+    assert_eq!(project.read("a.txt"), "one\n");
+}
+
+#[test]
+fn preserves_crlf_and_bom() {
+    let project = Project::new();
+    project.write("win.txt", "\u{feff}first\r\nsecond\r\nthird");
+
+    let mut patch = replace("win.txt", "second\n", "2nd\nand a half\n");
+    apply_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert_eq!(
+        project.read("win.txt"),
+        "\u{feff}first\r\n2nd\r\nand a half\r\nthird"
+    );
+}
+
+#[test]
+fn undo_after_later_edit_reverses_in_place() {
+    let project = Project::new();
+    project.write("a.txt", "alpha\nbeta\ngamma\n");
+
+    let mut patch = replace("a.txt", "beta", "BETA");
+    apply_patch(&mut patch, &project.sandbox);
+    project.write("a.txt", "alpha\nBETA\ngamma\ndelta\n");
+
+    undo_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Pending);
+    assert_eq!(project.read("a.txt"), "alpha\nbeta\ngamma\ndelta\n");
+}
+
+#[test]
+fn undo_after_an_edit_inside_the_patch_is_a_conflict() {
+    let project = Project::new();
+    project.write("a.rs", "fn a() {}\nlet t = (1, 2);\nfn b() {}\n");
+
+    let mut patch = replace("a.rs", "let t = (1, 2);", "let t = (1, 2, 3);");
+    apply_patch(&mut patch, &project.sandbox);
+    project.write("a.rs", "fn a() {}\nlet t = (1, 2, 4);\nfn b() {}\n");
+
+    undo_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Success);
+    assert!(patch.undo_conflict.is_some());
+    assert_eq!(
+        project.read("a.rs"),
+        "fn a() {}\nlet t = (1, 2, 4);\nfn b() {}\n"
+    );
+}
+
+#[test]
+fn undo_conflict_needs_a_forced_restore() {
+    let project = Project::new();
+    project.write("a.txt", "alpha\n");
+
+    let mut patch = replace("a.txt", "alpha", "beta");
+    apply_patch(&mut patch, &project.sandbox);
+    project.write("a.txt", "rewritten\n");
+
+    undo_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.undo_conflict.as_deref(), Some("rewritten\n"));
+    assert_eq!(project.read("a.txt"), "rewritten\n");
+
+    force_undo_patch(&mut patch, &project.sandbox);
+    assert_eq!(patch.status, PatchStatus::Pending);
+    assert_eq!(project.read("a.txt"), "alpha\n");
+}
+
+#[test]
+fn create_delete_and_rename_round_trip() {
+    let project = Project::new();
+    project.write("old.txt", "moving\n");
+    project.write("gone.txt", "bye\n");
+
+    let mut create = operation(PatchOperation::Create, "new/file.txt", "hello\n", None);
+    let mut delete = operation(PatchOperation::Delete, "gone.txt", "", None);
+    let mut rename = operation(PatchOperation::Rename, "old.txt", "", Some("moved/old.txt"));
+    for patch in [&mut create, &mut delete, &mut rename] {
+        apply_patch(patch, &project.sandbox);
+        assert_eq!(patch.status, PatchStatus::Success);
+    }
+    assert_eq!(project.read("new/file.txt"), "hello\n");
+    assert!(!project.exists("gone.txt"));
+    assert_eq!(project.read("moved/old.txt"), "moving\n");
+
+    for patch in [&mut rename, &mut delete, &mut create] {
+        undo_patch(patch, &project.sandbox);
+        assert_eq!(patch.status, PatchStatus::Pending);
+    }
+    assert!(!project.exists("new/file.txt"));
+    assert_eq!(project.read("gone.txt"), "bye\n");
+    assert_eq!(project.read("old.txt"), "moving\n");
+}
+
+#[test]
+fn create_refuses_to_overwrite() {
+    let project = Project::new();
+    project.write("a.txt", "keep\n");
+
+    let mut patch = operation(PatchOperation::Create, "a.txt", "clobber\n", None);
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert_eq!(project.read("a.txt"), "keep\n");
+}
+
+#[test]
+fn sandbox_protects_git_in_any_case() {
+    let project = Project::new();
+    for path in [
+        ".git/config",
+        ".GIT/config",
+        "src/.Git/hooks/pre-commit",
+        ".BetterPaste/journal/x.jsonl",
+    ] {
+        assert!(
+            project.sandbox.resolve(path).is_err(),
+            "{path} should be rejected"
+        );
+    }
+    assert!(project.sandbox.resolve("src/git.rs").is_ok());
+}
+
+#[test]
+fn failed_create_leaves_no_new_folders_behind() {
+    let project = Project::new();
+    project.write("src/keep.rs", "\n");
+
+    // The folders can be made, but the over-long file name cannot.
+    let path = format!("src/new/deeper/{}.txt", "x".repeat(300));
+    let mut patch = operation(PatchOperation::Create, &path, "hi\n", None);
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert!(!project.exists("src/new"));
+    assert!(project.exists("src/keep.rs"));
+}
+
+#[test]
+fn sandbox_keeps_parent_traversal_inside_the_root() {
+    let project = Project::new();
+    for path in [
+        "../outside.txt",
+        "src/../../outside.txt",
+        "/etc/passwd",
+        "./..",
+    ] {
+        assert!(
+            project.sandbox.resolve(path).is_err(),
+            "{path} should be rejected"
+        );
+    }
+    assert_eq!(
+        project.sandbox.resolve("src/../a.txt").unwrap(),
+        project.root().canonicalize().unwrap().join("a.txt")
+    );
+
+    let mut patch = operation(
+        PatchOperation::Create,
+        "../betterpaste-escape.txt",
+        "hi\n",
+        None,
+    );
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert!(!project.root().join("../betterpaste-escape.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn sandbox_rejects_symlinks_out_of_the_root() {
+    let project = Project::new();
+    let outside = Project::new();
+    outside.write("secret.txt", "keep\n");
+    std::os::unix::fs::symlink(outside.root(), project.root().join("linked")).unwrap();
+    std::os::unix::fs::symlink(
+        outside.root().join("secret.txt"),
+        project.root().join("secret.txt"),
+    )
+    .unwrap();
+
+    for path in ["linked/secret.txt", "linked/new.txt", "secret.txt"] {
+        assert!(
+            project.sandbox.resolve(path).is_err(),
+            "{path} should be rejected"
+        );
+    }
+    let mut patch = replace("secret.txt", "keep", "leaked");
+    apply_patch(&mut patch, &project.sandbox);
+    assert!(matches!(patch.status, PatchStatus::Failed(_)));
+    assert_eq!(outside.read("secret.txt"), "keep\n");
+}
+
+#[test]
+fn sandbox_rejects_control_characters() {
+    let project = Project::new();
+    assert!(project.sandbox.resolve("src/a.rs\nfn main() {}").is_err());
+    assert!(project.sandbox.resolve("src/a\u{7}.rs").is_err());
+}
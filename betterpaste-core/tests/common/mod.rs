@@ -0,0 +1,64 @@
+#![allow(dead_code)]
+
+use betterpaste_core::{AppConfig, IncomingPatch, PatchEntry, PatchOperation, PathSandbox};
+use std::{fs, path::Path};
+use tempfile::TempDir;
+
+/// A throwaway project directory with a sandbox rooted at it.
+pub struct Project {
+    pub dir: TempDir,
+    pub sandbox: PathSandbox,
+}
+
+impl Project {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = PathSandbox::new(dir.path().to_path_buf(), &AppConfig::default());
+        Self { dir, sandbox }
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn write(&self, relative: &str, content: &str) {
+        let path = self.root().join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.root().join(relative)).unwrap()
+    }
+
+    pub fn exists(&self, relative: &str) -> bool {
+        self.root().join(relative).exists()
+    }
+}
+
+pub fn replace(file_path: &str, search: &str, replace: &str) -> PatchEntry {
+    PatchEntry::new(IncomingPatch {
+        file_path: file_path.to_string(),
+        search_content: search.to_string(),
+        replace_content: replace.to_string(),
+        operation: PatchOperation::Replace,
+        new_path: None,
+        line_hint: None,
+    })
+}
+
+pub fn operation(
+    operation: PatchOperation,
+    file_path: &str,
+    content: &str,
+    new_path: Option<&str>,
+) -> PatchEntry {
+    PatchEntry::new(IncomingPatch {
+        file_path: file_path.to_string(),
+        search_content: String::new(),
+        replace_content: content.to_string(),
+        operation,
+        new_path: new_path.map(str::to_string),
+        line_hint: None,
+    })
+}
@@ -0,0 +1,259 @@
+use betterpaste_core::{
+    Symbol, SymbolKind, SymbolMode, SymbolModes, compress_code, outline_symbols, render_file,
+};
+use std::path::Path;
+
+fn compress(file: &str, source: &str) -> String {
+    compress_code(Path::new(file), source)
+}
+
+#[test]
+fn rust_keeps_fields_docs_and_braces() {
+    let source = r#"/// A point.
+#[derive(Debug)]
+pub struct Point {
+    pub x: i32,
+    /// Vertical.
+    pub y: i32,
+}
+
+impl Point {
+    /// Distance from the origin.
+    pub fn len(&self) -> f64 {
+        let s = "}";
+        let c = '{';
+        ((self.x * self.x + self.y * self.y) as f64).sqrt()
+    }
+
+    fn label<'a>(&'a self, f: fn(i32) -> i32) -> &'a str {
+        "point"
+    }
+}
+"#;
+    let expected = r#"/// A point.
+#[derive(Debug)]
+pub struct Point {
+    pub x: i32,
+    /// Vertical.
+    pub y: i32,
+}
+
+impl Point {
+    /// Distance from the origin.
+    pub fn len(&self) -> f64 { /* body removed */ }
+
+    fn label<'a>(&'a self, f: fn(i32) -> i32) -> &'a str { /* body removed */ }
+}
+"#;
+    assert_eq!(compress("src/point.rs", source), expected);
+}
+
+#[test]
+fn rust_keeps_traits_and_nested_modules() {
+    let source = "pub trait Shape {\n    fn area(&self) -> f64;\n    fn name(&self) -> &str { r#\"sh{ape\"# }\n}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn works() {\n        assert!(true);\n    }\n}\n";
+    let expected = "pub trait Shape {\n    fn area(&self) -> f64;\n    fn name(&self) -> &str { /* body removed */ }\n}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn works() { /* body removed */ }\n}\n";
+    assert_eq!(compress("lib.rs", source), expected);
+}
+
+#[test]
+fn python_keeps_docstrings_and_class_fields() {
+    let source = r#"import os
+
+
+class Config:
+    """Settings."""
+
+    name: str = "x"
+
+    def load(self, path: str) -> None:
+        """Reads the file.
+
+        Multi-line docstring.
+        """
+        text = """
+not indented
+"""
+        self.name = text
+
+    async def save(
+        self,
+    ):
+        pass
+
+
+# helpers
+def helper(): return 1
+"#;
+    let expected = r#"import os
+
+
+class Config:
+    """Settings."""
+
+    name: str = "x"
+
+    def load(self, path: str) -> None:
+        """Reads the file.
+
+        Multi-line docstring.
+        """
+        ...  # body removed
+
+    async def save(
+        self,
+    ):
+        ...  # body removed
+
+
+# helpers
+def helper(): return 1
+"#;
+    assert_eq!(compress("config.py", source), expected);
+}
+
+#[test]
+fn typescript_compresses_functions_methods_and_arrows() {
+    let source = r#"export interface User {
+  id: number;
+  name: string;
+}
+
+export class Service {
+  private users: User[] = [];
+
+  constructor(private readonly api: Api) {
+    this.init();
+  }
+
+  async load(id: number): Promise<User> {
+    const url = `/users/${id}/{raw}`;
+    if (id) { return this.api.get(url); }
+  }
+}
+
+export function format(user: User): string {
+  return `${user.name}`;
+}
+
+export const handler = (e: Event) => {
+  console.log(e);
+};
+
+const defaults = { retries: 3 };
+"#;
+    let expected = r#"export interface User {
+  id: number;
+  name: string;
+}
+
+export class Service {
+  private users: User[] = [];
+
+  constructor(private readonly api: Api) { /* body removed */ }
+
+  async load(id: number): Promise<User> { /* body removed */ }
+}
+
+export function format(user: User): string { /* body removed */ }
+
+export const handler = (e: Event) => { /* body removed */ };
+
+const defaults = { retries: 3 };
+"#;
+    assert_eq!(compress("src/service.ts", source), expected);
+}
+
+#[test]
+fn go_compresses_funcs_but_not_types() {
+    let source = "package main\n\ntype Server struct {\n\tAddr string\n\tOnStop func()\n}\n\nfunc (s *Server) Run(opts interface{}) error {\n\treturn nil\n}\n\nvar raw = `}`\n";
+    let expected = "package main\n\ntype Server struct {\n\tAddr string\n\tOnStop func()\n}\n\nfunc (s *Server) Run(opts interface{}) error { /* body removed */ }\n\nvar raw = `}`\n";
+    assert_eq!(compress("main.go", source), expected);
+}
+
+#[test]
+fn csharp_compresses_methods_and_accessors() {
+    let source = "namespace App\n{\n    public class Repo<T> where T : class\n    {\n        private readonly List<T> items = new List<T>() { };\n        public int Count { get; set; }\n        public string Name\n        {\n            get { return @\"a\"\"}\"; }\n        }\n\n        [HttpGet(\"x\")]\n        public T Find(int id) where T : class\n        {\n            return items[id];\n        }\n    }\n}\n";
+    let expected = "namespace App\n{\n    public class Repo<T> where T : class\n    {\n        private readonly List<T> items = new List<T>() { };\n        public int Count { get; set; }\n        public string Name\n        {\n            get { /* body removed */ }\n        }\n\n        [HttpGet(\"x\")]\n        public T Find(int id) where T : class\n        { /* body removed */ }\n    }\n}\n";
+    assert_eq!(compress("Repo.cs", source), expected);
+}
+
+#[test]
+fn unknown_languages_are_left_alone() {
+    let source = "{\n  \"a\": { \"b\": 1 }\n}\n";
+    assert_eq!(compress("data.json", source), source);
+}
+
+fn paths(symbols: &[Symbol]) -> Vec<String> {
+    symbols
+        .iter()
+        .flat_map(|s| std::iter::once(s.path.clone()).chain(paths(&s.children)))
+        .collect()
+}
+
+#[test]
+fn outlines_name_functions_types_and_members() {
+    let rust = "/// A point.\npub struct Point {\n    x: i32,\n}\n\nimpl Display for Point {\n    fn fmt(&self) {}\n}\n\nimpl Point {\n    fn new() -> Self { Point { x: 0 } }\n    fn new() {}\n}\n";
+    let symbols = outline_symbols(Path::new("lib.rs"), rust);
+    assert_eq!(
+        paths(&symbols),
+        [
+            "Point",
+            "impl Display for Point",
+            "impl Display for Point::fmt",
+            "impl Point",
+            "impl Point::new",
+            "impl Point::new #2"
+        ]
+    );
+    assert_eq!(symbols[0].kind, SymbolKind::Type);
+    assert!(rust[symbols[0].span.clone()].starts_with("/// A point."));
+
+    let go = "package main\n\nimport \"fmt\"\n\n// Run starts it.\nfunc (s *Server) Run() error {\n\treturn nil\n}\n\nvar handle = func(x int) {\n}\n";
+    let symbols = outline_symbols(Path::new("main.go"), go);
+    assert_eq!(paths(&symbols), ["Server.Run", "handle"]);
+    assert!(go[symbols[0].span.clone()].starts_with("// Run starts it."));
+
+    let ts = "export class Service {\n  constructor() {}\n  async load(id: number): Promise<User> { return x; }\n}\nexport const handler = (e: Event) => {\n};\nexport function format<T>(u: T) {}\n";
+    assert_eq!(
+        paths(&outline_symbols(Path::new("a.ts"), ts)),
+        [
+            "Service",
+            "Service::constructor",
+            "Service::load",
+            "handler",
+            "format"
+        ]
+    );
+
+    let py = "class Config:\n    @property\n    def name(self):\n        return 1\n\n\nasync def main():\n    pass\n";
+    let symbols = outline_symbols(Path::new("a.py"), py);
+    assert_eq!(paths(&symbols), ["Config", "Config::name", "main"]);
+    assert!(py[symbols[0].children[0].span.clone()].starts_with("    @property"));
+}
+
+#[test]
+fn renders_each_symbol_in_its_mode() {
+    let source = "struct Point {\n    x: i32,\n}\n\nimpl Point {\n    /// Makes one.\n    fn new() -> Self {\n        Point { x: 0 }\n    }\n\n    fn len(&self) -> i32 {\n        self.x\n    }\n}\n\nfn main() {\n    run();\n}\n";
+    let path = Path::new("main.rs");
+    let modes: SymbolModes = [
+        ("impl Point".to_string(), SymbolMode::Signature),
+        ("impl Point::len".to_string(), SymbolMode::Full),
+        ("main".to_string(), SymbolMode::Excluded),
+    ]
+    .into();
+    let expected = "struct Point {\n    x: i32,\n}\n\nimpl Point {\n    /// Makes one.\n    fn new() -> Self { /* body removed */ }\n\n    fn len(&self) -> i32 {\n        self.x\n    }\n}\n\n";
+    assert_eq!(render_file(path, source, false, &modes), expected);
+
+    // In a partial file, a `Full` override keeps one function whole.
+    let modes: SymbolModes = [("main".to_string(), SymbolMode::Full)].into();
+    let rendered = render_file(path, source, true, &modes);
+    assert!(rendered.contains("fn len(&self) -> i32 { /* body removed */ }"));
+    assert!(rendered.ends_with("fn main() {\n    run();\n}\n"));
+
+    let py = "class A:\n    def keep(self):\n        return 1\n\n    def drop(self):\n        return 2\n";
+    let modes: SymbolModes = [("A::drop".to_string(), SymbolMode::Excluded)].into();
+    assert_eq!(
+        render_file(Path::new("a.py"), py, false, &modes),
+        "class A:\n    def keep(self):\n        return 1\n\n"
+    );
+}
@@ -0,0 +1,49 @@
+use betterpaste_core::{AppConfig, MAX_RECENT_PROJECTS};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn recent_projects_are_most_recent_first_without_duplicates() {
+    let mut config = AppConfig::default();
+    config.remember_project(Path::new("/a"));
+    config.remember_project(Path::new("/b"));
+    config.remember_project(Path::new("/a"));
+    assert_eq!(
+        config.recent_projects,
+        [PathBuf::from("/a"), PathBuf::from("/b")]
+    );
+
+    for i in 0..MAX_RECENT_PROJECTS + 5 {
+        config.remember_project(&PathBuf::from(format!("/p{}", i)));
+    }
+    assert_eq!(config.recent_projects.len(), MAX_RECENT_PROJECTS);
+    assert_eq!(
+        config.recent_projects[0],
+        PathBuf::from(format!("/p{}", MAX_RECENT_PROJECTS + 4))
+    );
+}
+
+#[test]
+fn api_token_is_generated_once_and_checked() {
+    let mut config = AppConfig::default();
+    assert!(!config.token_matches(""));
+    assert!(config.ensure_api_token());
+    let token = config.api_token.clone();
+    assert_eq!(token.len(), 48);
+    assert!(!config.ensure_api_token());
+    assert_eq!(config.api_token, token);
+
+    assert!(config.token_matches(&token));
+    assert!(!config.token_matches(&token[1..]));
+    let last = if token.ends_with('0') { "1" } else { "0" };
+    assert!(!config.token_matches(&format!("{}{}", &token[..47], last)));
+
+    assert!(config.origin_allowed(None));
+    assert!(config.origin_allowed(Some("https://claude.ai")));
+    assert!(!config.origin_allowed(Some("moz-extension://1234")));
+    config
+        .allowed_origins
+        .push("moz-extension://1234/".to_string());
+    assert!(config.origin_allowed(Some("moz-extension://1234")));
+    assert!(!config.origin_allowed(Some("https://evil.example")));
+    assert!(!config.origin_allowed(Some("null")));
+}
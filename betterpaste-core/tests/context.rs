@@ -0,0 +1,184 @@
+mod common;
+
+use betterpaste_core::{
+    AppConfig, ContextSelection, ExtractOutcome, ModelBudget, Preset, SkipReason, SymbolMode,
+    SymbolModes, build_context, extract_files, generate_xml, scan_files,
+};
+use common::Project;
+use std::{collections::HashMap, path::PathBuf};
+
+#[test]
+fn scan_skips_config_and_journal() {
+    let project = Project::new();
+    project.write("src/main.rs", "fn main() {}\n");
+    project.write("betterPaste_config.json", "{}");
+    project.write(".betterpaste/journal/2024-01-01.jsonl", "");
+
+    assert_eq!(scan_files(project.root()), [PathBuf::from("src/main.rs")]);
+}
+
+#[test]
+fn generated_context_extracts_into_an_empty_project() {
+    let source = Project::new();
+    source.write("src/lib.rs", "pub fn a() {}\n");
+    source.write("notes.txt", "left out\n");
+    std::fs::write(
+        source.root().join("logo.png"),
+        [0x89, b'P', b'N', b'G', 0xff, 0xfe],
+    )
+    .unwrap();
+    let files = scan_files(source.root());
+    let selected: HashMap<PathBuf, bool> = files
+        .iter()
+        .map(|f| (f.clone(), !f.ends_with("notes.txt")))
+        .collect();
+    let xml = generate_xml(
+        source.root(),
+        &files,
+        &selected,
+        &HashMap::new(),
+        &HashMap::new(),
+        &AppConfig::default(),
+    );
+    assert!(xml.contains("<File path=\"src/lib.rs\" compressed=\"false\">"));
+    assert!(!xml.contains("notes.txt"));
+    assert!(xml.contains("<File path=\"logo.png\" skipped=\"binary\"/>"));
+
+    let target = Project::new();
+    let dry = extract_files(&xml, &target.sandbox, true);
+    assert_eq!(dry, [("src/lib.rs".to_string(), ExtractOutcome::New)]);
+    assert!(!target.exists("src/lib.rs"));
+
+    extract_files(&xml, &target.sandbox, false);
+    assert_eq!(target.read("src/lib.rs"), "pub fn a() {}");
+    let again = extract_files(&xml, &target.sandbox, false);
+    assert_eq!(again, [("src/lib.rs".to_string(), ExtractOutcome::Exists)]);
+}
+
+#[test]
+fn symbol_modes_shape_the_generated_file() {
+    let project = Project::new();
+    project.write(
+        "src/lib.rs",
+        "pub fn keep() {\n    1;\n}\n\npub fn skim() {\n    2;\n}\n\nfn secret() {}\n",
+    );
+    let files = scan_files(project.root());
+    let selected: HashMap<PathBuf, bool> = files.iter().map(|f| (f.clone(), true)).collect();
+    let modes: SymbolModes = [
+        ("skim".to_string(), SymbolMode::Signature),
+        ("secret".to_string(), SymbolMode::Excluded),
+    ]
+    .into();
+    let symbols = HashMap::from([(PathBuf::from("src/lib.rs"), modes)]);
+    let xml = generate_xml(
+        project.root(),
+        &files,
+        &selected,
+        &HashMap::new(),
+        &symbols,
+        &AppConfig::default(),
+    );
+    assert!(xml.contains(
+        "<File path=\"src/lib.rs\" compressed=\"true\">\npub fn keep() {\n    1;\n}\n\npub fn skim() { /* body removed */ }\n\n\n</File>"
+    ));
+}
+
+#[test]
+fn built_context_follows_the_preset_and_leaves_skipped_files_out() {
+    let project = Project::new();
+    project.write("src/lib.rs", "pub fn a() {}\n");
+    project.write("docs/notes.md", "notes\n");
+    std::fs::write(
+        project.root().join("src/logo.png"),
+        [0x89, b'P', b'N', b'G', 0xff, 0xfe],
+    )
+    .unwrap();
+    let preset = Preset {
+        name: "src".to_string(),
+        include_globs: vec!["src/**".to_string()],
+        instructions: Some("Only the library.".to_string()),
+        ..Preset::default()
+    };
+    let config = AppConfig::default();
+
+    let context = build_context(project.root(), &config, Some(&preset), None, false);
+    assert_eq!(context.files, 1);
+    assert!(context.xml.contains("Only the library."));
+    assert!(context.xml.contains("<File path=\"src/lib.rs\""));
+    assert!(!context.xml.contains("<File path=\"docs/notes.md\""));
+    assert!(!context.xml.contains("<File path=\"src/logo.png\""));
+    assert_eq!(
+        context.skipped,
+        [(PathBuf::from("src/logo.png"), SkipReason::Binary)]
+    );
+    assert!(context.tokens > 0);
+    assert_eq!(context.budget, config.token_budget());
+
+    let everything = build_context(project.root(), &config, Some(&preset), None, true);
+    assert_eq!(everything.files, 2);
+    assert!(
+        everything
+            .xml
+            .contains("<File path=\"src/logo.png\" skipped=\"binary\"/>")
+    );
+}
+
+#[test]
+fn built_context_uses_the_published_selection_and_fits_the_budget() {
+    let project = Project::new();
+    project.write(
+        "a.rs",
+        &"pub fn a() {\n    let x = 1;\n    let y = x + 1;\n    println!(\"{x} {y}\");\n}\n"
+            .repeat(400),
+    );
+    project.write("b.rs", "pub fn b() {}\n");
+    let selection = ContextSelection {
+        selected: HashMap::from([
+            (PathBuf::from("a.rs"), true),
+            (PathBuf::from("b.rs"), false),
+        ]),
+        ..ContextSelection::default()
+    };
+    let mut config = AppConfig {
+        model_budgets: vec![ModelBudget {
+            model: "small".to_string(),
+            max_tokens: 2000,
+        }],
+        target_model: "small".to_string(),
+        ..AppConfig::default()
+    };
+
+    let full = build_context(
+        project.root(),
+        &config,
+        None,
+        Some(selection.clone()),
+        false,
+    );
+    assert_eq!(full.files, 1);
+    assert_eq!(full.budget, Some(2000));
+    assert!(full.tokens > 2000);
+    assert!(
+        full.xml
+            .contains("<File path=\"a.rs\" compressed=\"false\">")
+    );
+    assert!(!full.xml.contains("<File path=\"b.rs\""));
+
+    config.auto_partial = true;
+    let fitted = build_context(project.root(), &config, None, Some(selection), false);
+    assert!(
+        fitted
+            .xml
+            .contains("<File path=\"a.rs\" compressed=\"true\">")
+    );
+    assert_eq!(fitted.fitted, [PathBuf::from("a.rs")]);
+    assert!(fitted.tokens < full.tokens);
+}
+
+#[test]
+fn extraction_rejects_paths_outside_the_root() {
+    let project = Project::new();
+    let xml = "<File path=\"../escape.txt\">\nnope\n</File>";
+    let outcomes = extract_files(xml, &project.sandbox, false);
+    assert!(matches!(outcomes[0].1, ExtractOutcome::Rejected(_)));
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use betterpaste_core::{AppConfig, SkipReason, detect_skips, scan_files};
+use common::Project;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+fn skips(project: &Project, config: &AppConfig) -> HashMap<PathBuf, SkipReason> {
+    detect_skips(project.root(), &scan_files(project.root()), config)
+}
+
+#[test]
+fn binary_large_and_minified_files_are_skipped() {
+    let project = Project::new();
+    project.write("src/main.rs", "fn main() {}\n");
+    // The 8 KB sample ends in the middle of an "é".
+    project.write("notes/é.txt", &"\né".repeat(4000));
+    fs::write(
+        project.root().join("logo.png"),
+        [0x89, b'P', b'N', b'G', 0, 0, 0x1a],
+    )
+    .unwrap();
+    fs::write(
+        project.root().join("latin1.txt"),
+        [b'c', b'a', b'f', 0xe9, b'\n'],
+    )
+    .unwrap();
+    project.write("big.sql", &"insert into t values (1);\n".repeat(700));
+    project.write("web/bundle.js", &"var a=1;".repeat(400));
+
+    let config = AppConfig {
+        max_file_size_kb: 16,
+        ..AppConfig::default()
+    };
+    let skipped = skips(&project, &config);
+    assert_eq!(skipped.len(), 4, "{:?}", skipped);
+    assert_eq!(skipped[&PathBuf::from("logo.png")], SkipReason::Binary);
+    assert_eq!(skipped[&PathBuf::from("latin1.txt")], SkipReason::Binary);
+    assert_eq!(
+        skipped[&PathBuf::from("big.sql")],
+        SkipReason::TooLarge { bytes: 18200 }
+    );
+    assert_eq!(
+        skipped[&PathBuf::from("web/bundle.js")],
+        SkipReason::Minified
+    );
+
+    // Without a size cap only the size check goes away.
+    let config = AppConfig {
+        max_file_size_kb: 0,
+        ..AppConfig::default()
+    };
+    assert!(!skips(&project, &config).contains_key(&PathBuf::from("big.sql")));
+}
+
+#[test]
+fn generated_files_come_from_globs_and_gitattributes() {
+    let project = Project::new();
+    project.write("Cargo.lock", "# lock\n");
+    project.write("src/lib.rs", "pub fn a() {}\n");
+    project.write("src/gen/schema.rs", "pub struct A;\n");
+    project.write("src/gen/keep.rs", "pub struct B;\n");
+    project.write("web/api.ts", "export {}\n");
+    project.write(
+        ".gitattributes",
+        "# generated code\nsrc/gen/** linguist-generated\n*.ts linguist-vendored=true\n",
+    );
+    project.write("src/gen/.gitattributes", "keep.rs -linguist-generated\n");
+
+    let skipped = skips(&project, &AppConfig::default());
+    let mut names: Vec<String> = skipped
+        .iter()
+        .map(|(f, r)| format!("{} {}", f.display(), r))
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "Cargo.lock generated (**/Cargo.lock)",
+            "src/gen/schema.rs generated (.gitattributes)",
+            "web/api.ts generated (.gitattributes)",
+        ]
+    );
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use betterpaste_core::{
+    AppConfig, EventKind, PatchAction, ReceiptStatus, SharedAppState, act_on_patch, receive_group,
+    receive_patch, resume_queue, track_changes,
+};
+use common::{Project, replace};
+
+#[test]
+fn operations_report_their_lifecycle_events() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    let mut state = SharedAppState::new(project.root().to_path_buf(), &AppConfig::default());
+    // Nothing is reported before anyone subscribes.
+    receive_patch(state.active_mut(), replace("a.txt", "x", "y").data);
+    let queue = state.subscribe();
+    let events = || queue.try_iter().map(|e| e.kind).collect::<Vec<_>>();
+    assert!(events().is_empty());
+
+    receive_patch(state.active_mut(), replace("a.txt", "one", "1").data);
+    assert_eq!(events(), [EventKind::Received, EventKind::Applied]);
+    act_on_patch(state.active_mut(), 1, PatchAction::Undo { force: false }).unwrap();
+    assert_eq!(events(), [EventKind::Undone]);
+
+    track_changes(state.active_mut(), |w| w.is_paused = true);
+    receive_patch(state.active_mut(), replace("a.txt", "missing", "2").data);
+    assert_eq!(
+        events(),
+        [EventKind::Paused, EventKind::Received, EventKind::Queued]
+    );
+    resume_queue(state.active_mut());
+    assert_eq!(events(), [EventKind::Paused, EventKind::Failed]);
+
+    // Transitions that follow each other quickly are all reported, in order.
+    act_on_patch(state.active_mut(), 1, PatchAction::Apply { line: None }).unwrap();
+    act_on_patch(state.active_mut(), 1, PatchAction::Undo { force: false }).unwrap();
+    assert_eq!(events(), [EventKind::Applied, EventKind::Undone]);
+
+    track_changes(state.active_mut(), |w| w.auto_apply = false);
+    act_on_patch(state.active_mut(), 2, PatchAction::Dismiss).unwrap();
+    let changes: Vec<_> = queue.try_iter().collect();
+    assert_eq!(
+        changes.iter().map(|e| e.kind).collect::<Vec<_>>(),
+        [EventKind::AutoApply, EventKind::Dismissed]
+    );
+    assert_eq!(changes[0].enabled, Some(false));
+    assert_eq!(changes[1].patch.as_ref().unwrap().file_path, "a.txt");
+
+    // Patches auto-dismiss drops never reach the list, but are still reported.
+    track_changes(state.active_mut(), |w| w.auto_dismiss = true);
+    receive_group(state.active_mut(), vec![replace("a.txt", "one", "1").data]);
+    let changes: Vec<_> = queue.try_iter().collect();
+    assert_eq!(
+        changes.iter().map(|e| e.kind).collect::<Vec<_>>(),
+        [
+            EventKind::AutoDismiss,
+            EventKind::Received,
+            EventKind::Dismissed
+        ]
+    );
+    assert_eq!(
+        changes[2].patch.as_ref().unwrap().status,
+        ReceiptStatus::Dismissed
+    );
+
+    // Projects opened later report too, but their restored history is not news.
+    let other = Project::new();
+    other.write("b.txt", "x\n");
+    let index = state.open(other.root().to_path_buf(), &AppConfig::default());
+    state.workspaces[index]
+        .patches
+        .push(replace("b.txt", "x", "y"));
+    assert!(events().is_empty());
+    receive_patch(
+        &mut state.workspaces[index],
+        replace("b.txt", "x", "z").data,
+    );
+    let changes: Vec<_> = queue.try_iter().collect();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].project, state.workspaces[index].id);
+}
@@ -0,0 +1,40 @@
+use betterpaste_core::FileFilter;
+use std::path::Path;
+
+fn matching<'a>(filter: &str, paths: &[&'a str]) -> Vec<&'a str> {
+    let filter = FileFilter::parse(filter).unwrap();
+    paths
+        .iter()
+        .copied()
+        .filter(|p| filter.matches(Path::new(p)))
+        .collect()
+}
+
+#[test]
+fn filters_by_glob_regex_or_text() {
+    let paths = [
+        "src/main.rs",
+        "src/api/routes.rs",
+        "src/api/routes_test.rs",
+        "web/App.tsx",
+        "README.md",
+    ];
+    assert_eq!(matching("", &paths), paths);
+    assert_eq!(
+        matching("src/**/*.rs", &paths),
+        ["src/main.rs", "src/api/routes.rs", "src/api/routes_test.rs"]
+    );
+    assert_eq!(matching("*test*", &paths), ["src/api/routes_test.rs"]);
+    assert_eq!(
+        matching("re:^(web|src/api)/[a-z]+\\.", &paths),
+        ["src/api/routes.rs"]
+    );
+    assert_eq!(
+        matching("/\\.(md|tsx)$/", &paths),
+        ["web/App.tsx", "README.md"]
+    );
+    assert_eq!(matching("app", &paths), ["web/App.tsx"]);
+
+    assert!(FileFilter::parse("re:(").is_err());
+    assert!(FileFilter::parse("src/[").is_err());
+}
@@ -0,0 +1,320 @@
+mod common;
+
+use betterpaste_core::{
+    AppConfig, MatchTier, PatchAction, PatchStatus, PathSandbox, ReceiptStatus, Workspace,
+    act_on_patch, apply_group, apply_patch, journal_dismissed, load_journal, push_group,
+    receive_group, receive_patch, restore_patches, undo_group, undo_patch,
+};
+use common::{Project, replace};
+
+fn state_for(project: &Project) -> Workspace {
+    let sandbox = PathSandbox::new(project.root().to_path_buf(), &AppConfig::default());
+    let mut workspace = Workspace::new("test".to_string(), sandbox);
+    workspace.auto_apply = false;
+    workspace
+}
+
+#[test]
+fn group_applies_all_blocks() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    project.write("b.txt", "two\n");
+    let mut state = state_for(&project);
+
+    let blocks = vec![
+        replace("a.txt", "one", "1").data,
+        replace("b.txt", "two", "2").data,
+    ];
+    let group_id = push_group(&mut state, blocks).unwrap();
+    apply_group(&mut state, &group_id);
+    assert!(
+        state
+            .patches
+            .iter()
+            .all(|p| p.status == PatchStatus::Success)
+    );
+    assert_eq!(project.read("a.txt"), "1\n");
+    assert_eq!(project.read("b.txt"), "2\n");
+
+    undo_group(&mut state, &group_id);
+    assert_eq!(project.read("a.txt"), "one\n");
+    assert_eq!(project.read("b.txt"), "two\n");
+}
+
+#[test]
+fn group_with_a_bad_block_writes_nothing() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    project.write("b.txt", "two\n");
+    let mut state = state_for(&project);
+
+    let blocks = vec![
+        replace("a.txt", "one", "1").data,
+        replace("b.txt", "missing", "2").data,
+    ];
+    let group_id = push_group(&mut state, blocks).unwrap();
+    apply_group(&mut state, &group_id);
+    assert!(
+        state.groups[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Block 2 of 2 rejected")
+    );
+    assert_eq!(project.read("a.txt"), "one\n");
+    assert_eq!(project.read("b.txt"), "two\n");
+}
+
+#[test]
+fn receipts_report_what_became_of_each_patch() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    project.write("b.txt", "two\n");
+    let mut state = state_for(&project);
+    state.auto_apply = true;
+
+    let blocks = vec![
+        replace("a.txt", "one", "1").data,
+        replace("b.txt", "missing", "2").data,
+    ];
+    let receipts = receive_group(&mut state, blocks);
+    let statuses: Vec<_> = receipts.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, [ReceiptStatus::Pending, ReceiptStatus::Failed]);
+    assert_eq!(receipts[0].reason, state.groups[0].error);
+    assert_eq!(receipts[1].id.as_ref(), Some(&state.patches[1].id));
+    assert!(receipts[1].reason.is_some());
+
+    let receipt = receive_patch(&mut state, replace("a.txt", "one", "1").data);
+    assert_eq!(receipt.to_string(), "a.txt: applied");
+    assert_eq!(
+        (receipt.status, receipt.reason),
+        (ReceiptStatus::Success, None)
+    );
+
+    state.is_paused = true;
+    assert_eq!(
+        receive_patch(&mut state, replace("a.txt", "1", "one").data).status,
+        ReceiptStatus::Queued
+    );
+
+    state.auto_dismiss = true;
+    let receipts = receive_group(&mut state, vec![replace("b.txt", "two", "2").data]);
+    assert_eq!(
+        (receipts[0].status, receipts[0].id.as_ref()),
+        (ReceiptStatus::Dismissed, None)
+    );
+    assert_eq!(
+        receipts[0].to_string(),
+        "b.txt: auto-dismissed: auto-dismiss is on"
+    );
+    assert_eq!(state.patches.len(), 4);
+}
+
+#[test]
+fn patch_actions_follow_the_patcher_buttons() {
+    let project = Project::new();
+    project.write("a.txt", "x\nx\n");
+    let mut state = state_for(&project);
+    state.patches.push(replace("a.txt", "x", "y"));
+
+    assert!(act_on_patch(&mut state, 0, PatchAction::Undo { force: false }).is_err());
+    let receipt = act_on_patch(&mut state, 0, PatchAction::Apply { line: None }).unwrap();
+    assert_eq!(receipt.status, ReceiptStatus::Failed);
+    assert!(
+        act_on_patch(&mut state, 0, PatchAction::Apply { line: None })
+            .unwrap_err()
+            .contains("lines 1, 2")
+    );
+    let receipt = act_on_patch(&mut state, 0, PatchAction::Apply { line: Some(2) }).unwrap();
+    assert_eq!(receipt.status, ReceiptStatus::Success);
+    assert_eq!(project.read("a.txt"), "x\ny\n");
+    assert!(act_on_patch(&mut state, 0, PatchAction::Apply { line: None }).is_err());
+    assert!(act_on_patch(&mut state, 0, PatchAction::Dismiss).is_err());
+
+    project.write("a.txt", "edited\n");
+    assert!(act_on_patch(&mut state, 0, PatchAction::Undo { force: false }).is_err());
+    assert!(state.patches[0].undo_conflict.is_some());
+    let receipt = act_on_patch(&mut state, 0, PatchAction::Undo { force: true }).unwrap();
+    assert_eq!(receipt.status, ReceiptStatus::Pending);
+    assert_eq!(project.read("a.txt"), "x\nx\n");
+
+    let id = state.patches[0].id.clone();
+    let receipt = act_on_patch(&mut state, 0, PatchAction::Dismiss).unwrap();
+    assert_eq!(
+        (receipt.status, receipt.id),
+        (ReceiptStatus::Dismissed, Some(id))
+    );
+    assert!(state.patches.is_empty());
+    assert_eq!(load_journal(project.root()).len(), 0);
+}
+
+#[test]
+fn patch_actions_on_a_block_act_on_its_group() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    project.write("b.txt", "two\n");
+    let mut state = state_for(&project);
+    let blocks = vec![
+        replace("a.txt", "one", "1").data,
+        replace("b.txt", "two", "2").data,
+    ];
+    push_group(&mut state, blocks).unwrap();
+
+    act_on_patch(&mut state, 0, PatchAction::Apply { line: None }).unwrap();
+    assert_eq!(
+        (
+            project.read("a.txt").as_str(),
+            project.read("b.txt").as_str()
+        ),
+        ("1\n", "2\n")
+    );
+    let error = act_on_patch(&mut state, 1, PatchAction::Dismiss).unwrap_err();
+    assert!(error.contains(state.groups[0].id.as_str()));
+
+    act_on_patch(&mut state, 1, PatchAction::Undo { force: false }).unwrap();
+    assert_eq!(
+        (
+            project.read("a.txt").as_str(),
+            project.read("b.txt").as_str()
+        ),
+        ("one\n", "two\n")
+    );
+
+    project.write("b.txt", "gone\n");
+    let error = act_on_patch(&mut state, 0, PatchAction::Apply { line: None }).unwrap_err();
+    assert!(error.starts_with("Response group"));
+    assert_eq!(project.read("a.txt"), "one\n");
+
+    act_on_patch(&mut state, 0, PatchAction::Dismiss).unwrap();
+    assert!(state.patches.is_empty() && state.groups.is_empty());
+}
+
+#[test]
+fn ambiguous_block_holds_its_group_until_a_match_is_picked() {
+    let project = Project::new();
+    project.write("a.txt", "x\nz\nx\n");
+    project.write("b.txt", "two\n");
+    let mut state = state_for(&project);
+    state.auto_apply = true;
+
+    let blocks = vec![
+        replace("a.txt", "x", "y").data,
+        replace("b.txt", "two", "2").data,
+    ];
+    let receipts = receive_group(&mut state, blocks);
+    assert_eq!(state.patches[0].status, PatchStatus::Ambiguous(vec![1, 3]));
+    assert_eq!(state.patches[1].status, PatchStatus::Pending);
+    assert!(
+        receipts[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .contains("lines 1, 3")
+    );
+    assert_eq!(project.read("b.txt"), "two\n");
+
+    assert!(
+        act_on_patch(&mut state, 0, PatchAction::Apply { line: None })
+            .unwrap_err()
+            .contains("lines 1, 3")
+    );
+    let receipt = act_on_patch(&mut state, 0, PatchAction::Apply { line: Some(3) }).unwrap();
+    assert_eq!(receipt.status, ReceiptStatus::Success);
+    assert_eq!(project.read("a.txt"), "x\nz\ny\n");
+    assert_eq!(project.read("b.txt"), "2\n");
+    assert_eq!(state.groups[0].error, None);
+}
+
+#[test]
+fn auto_apply_leaves_fuzzy_matches_for_review() {
+    let project = Project::new();
+    project.write("a.rs", "let retries = 10;\n");
+    project.write("b.rs", "let delay = 5;\n");
+    let mut state = state_for(&project);
+    state.auto_apply = true;
+
+    let receipt = receive_patch(
+        &mut state,
+        replace("a.rs", "let retries = 11;", "let retries = 3;").data,
+    );
+    assert_eq!(receipt.status, ReceiptStatus::Pending);
+    assert!(
+        receipt
+            .warning
+            .as_deref()
+            .unwrap()
+            .contains("fuzzy match (94%) at line 1")
+    );
+    assert_eq!(
+        state.patches[0].match_info.as_ref().unwrap().tier,
+        MatchTier::Fuzzy
+    );
+    assert_eq!(project.read("a.rs"), "let retries = 10;\n");
+
+    let blocks = vec![
+        replace("b.rs", "let delay = 5;", "let delay = 1;").data,
+        replace("a.rs", "let retries = 11;", "let retries = 3;").data,
+    ];
+    receive_group(&mut state, blocks);
+    assert!(
+        state.groups[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Block 2 of 2 needs review")
+    );
+    assert_eq!(project.read("b.rs"), "let delay = 5;\n");
+
+    act_on_patch(&mut state, 0, PatchAction::Apply { line: None }).unwrap();
+    assert_eq!(project.read("a.rs"), "let retries = 3;\n");
+}
+
+#[test]
+fn journal_restores_applied_patches() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    project.write("b.txt", "two\n");
+
+    let mut kept = replace("a.txt", "one", "1");
+    let mut dismissed = replace("b.txt", "two", "2");
+    apply_patch(&mut kept, &project.sandbox);
+    apply_patch(&mut dismissed, &project.sandbox);
+    journal_dismissed(project.root(), &dismissed);
+
+    let mut restored = load_journal(project.root());
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].id, kept.id);
+    assert_eq!(restored[0].status, PatchStatus::Success);
+
+    undo_patch(&mut restored[0], &project.sandbox);
+    assert_eq!(project.read("a.txt"), "one\n");
+    assert_eq!(load_journal(project.root())[0].status, PatchStatus::Pending);
+}
+
+#[test]
+fn journal_restores_response_groups() {
+    let project = Project::new();
+    project.write("a.txt", "one\n");
+    project.write("b.txt", "two\n");
+    let mut state = state_for(&project);
+    let blocks = vec![
+        replace("a.txt", "one", "1").data,
+        replace("b.txt", "two", "2").data,
+    ];
+    let group_id = push_group(&mut state, blocks).unwrap();
+    apply_group(&mut state, &group_id);
+
+    let mut restarted = state_for(&project);
+    restore_patches(&mut restarted, load_journal(project.root()));
+    assert_eq!(restarted.groups.len(), 1);
+    assert!(
+        restarted
+            .patches
+            .iter()
+            .all(|p| p.group_id.as_deref() == Some(group_id.as_str()))
+    );
+
+    undo_group(&mut restarted, &group_id);
+    assert_eq!(project.read("a.txt"), "one\n");
+    assert_eq!(project.read("b.txt"), "two\n");
+}
@@ -0,0 +1,71 @@
+use betterpaste_core::{PatchOperation, parse_patch_blocks};
+
+#[test]
+fn parses_replace_blocks_and_file_operations_in_order() {
+    let text = "Here you go:\n```rust\n[<(x{START}x)>]\nsrc/a.rs\n[<(x{SEARCH}x)>]\nold\n[<(x{REPLACEWITH}x)>]\nnew\n[<(x{END}x)>]\n\n[<(x{CREATE}x)>]\nsrc/b.rs\n[<(x{CONTENT}x)>]\nfn b() {}\n[<(x{END}x)>]\n\n[<(x{DELETE}x)>]\nsrc/c.rs\n[<(x{END}x)>]\n\n[<(x{RENAME}x)>]\nsrc/d.rs\n[<(x{TO}x)>]\nsrc/e.rs\n[<(x{END}x)>]\n```\n";
+
+    let patches = parse_patch_blocks(text);
+    let ops: Vec<PatchOperation> = patches.iter().map(|p| p.operation).collect();
+    assert_eq!(
+        ops,
+        [
+            PatchOperation::Replace,
+            PatchOperation::Create,
+            PatchOperation::Delete,
+            PatchOperation::Rename
+        ]
+    );
+    assert_eq!(patches[0].file_path, "src/a.rs");
+    assert_eq!(patches[0].search_content, "old");
+    assert_eq!(patches[0].replace_content, "new");
+    assert_eq!(patches[1].replace_content, "fn b() {}\n");
+    assert_eq!(patches[3].new_path.as_deref(), Some("src/e.rs"));
+}
+
+#[test]
+fn blocks_round_trip_through_to_block() {
+    let text = "[<(x{RENAME}x)>]\nsrc/d.rs\n[<(x{TO}x)>]\nsrc/e.rs\n[<(x{END}x)>]";
+    let patch = parse_patch_blocks(text).remove(0);
+    let again = parse_patch_blocks(&patch.to_block()).remove(0);
+    assert_eq!(again.file_path, "src/d.rs");
+    assert_eq!(again.new_path.as_deref(), Some("src/e.rs"));
+}
+
+#[test]
+fn create_content_keeps_its_trailing_newline_through_to_block() {
+    let text = "[<(x{CREATE}x)>]\nsrc/b.rs\n[<(x{CONTENT}x)>]\nfn b() {}\n\n  [<(x{END}x)>]";
+    let patch = parse_patch_blocks(text).remove(0);
+    assert_eq!(patch.replace_content, "fn b() {}\n\n");
+    assert_eq!(
+        parse_patch_blocks(&patch.to_block())
+            .remove(0)
+            .replace_content,
+        "fn b() {}\n\n"
+    );
+}
+
+#[test]
+fn ignores_the_example_inside_replacing_rules() {
+    let text = "<ReplacingRules>\n[<(x{DELETE}x)>]\nsrc/example.rs\n[<(x{END}x)>]\n--- a/x.rs\n+++ b/x.rs\n@@ -1 +1 @@\n-a\n+b\n</ReplacingRules>\n[<(x{DELETE}x)>]\nsrc/real.rs\n[<(x{END}x)>]";
+    let patches = parse_patch_blocks(text);
+    assert_eq!(patches.len(), 1);
+    assert_eq!(patches[0].file_path, "src/real.rs");
+}
+
+#[test]
+fn parses_unified_diff_hunks_with_line_hints() {
+    let text = "```diff\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -2,3 +2,3 @@\n fn a() {}\n-fn b() {}\n+fn b() -> u8 { 1 }\n fn c() {}\n```\n";
+
+    let patches = parse_patch_blocks(text);
+    assert_eq!(patches.len(), 1);
+    assert_eq!(patches[0].operation, PatchOperation::Replace);
+    assert_eq!(patches[0].file_path, "src/lib.rs");
+    assert_eq!(patches[0].line_hint, Some(2));
+    assert!(patches[0].search_content.contains("fn b() {}"));
+    assert!(patches[0].replace_content.contains("fn b() -> u8 { 1 }"));
+}
+
+#[test]
+fn ignores_text_without_patches() {
+    assert!(parse_patch_blocks("Nothing to change here.").is_empty());
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use betterpaste_core::{
+    AppConfig, PRESETS_FILE, Preset, delete_preset, load_presets, save_preset, scan_files,
+};
+use common::Project;
+use std::{collections::HashMap, path::PathBuf};
+
+#[test]
+fn presets_select_files_and_override_instructions() {
+    let project = Project::new();
+    project.write("src/api/routes.rs", "");
+    project.write("src/api/routes_test.rs", "");
+    project.write("src/db.rs", "");
+    project.write("web/app.ts", "");
+    let preset = Preset {
+        name: "api-layer".to_string(),
+        include_globs: vec!["src/**".to_string()],
+        exclude_globs: vec!["**/*_test.rs".to_string()],
+        partial_globs: vec!["src/db.rs".to_string(), "web/**".to_string()],
+        instructions: Some("Only touch the API.".to_string()),
+        replacing_rules: None,
+    };
+
+    let files = scan_files(project.root());
+    let (selected, partials) = preset.selection(&files);
+    let picked = |map: &HashMap<PathBuf, bool>| {
+        let mut picked: Vec<_> = map
+            .iter()
+            .filter(|(_, on)| **on)
+            .map(|(f, _)| f.clone())
+            .collect();
+        picked.sort();
+        picked
+    };
+    assert_eq!(
+        picked(&selected),
+        [
+            PathBuf::from("src/api/routes.rs"),
+            PathBuf::from("src/db.rs")
+        ]
+    );
+    assert_eq!(picked(&partials), [PathBuf::from("src/db.rs")]);
+
+    let config = preset.apply_to(&AppConfig::default());
+    assert_eq!(config.instructions, "Only touch the API.");
+    assert_eq!(config.replacing_rules, AppConfig::default().replacing_rules);
+}
+
+#[test]
+fn presets_are_saved_per_project() {
+    let project = Project::new();
+    assert_eq!(load_presets(project.root()), Ok(Vec::new()));
+
+    let files = vec![PathBuf::from("a[1].rs"), PathBuf::from("b.rs")];
+    let selected = HashMap::from([(files[0].clone(), true), (files[1].clone(), false)]);
+    let partials = HashMap::from([(files[0].clone(), true), (files[1].clone(), false)]);
+    let ui_only = Preset::from_selection("ui-only", &files, &selected, &partials).unwrap();
+    // Exact paths are escaped, so they only ever match themselves.
+    assert_eq!(ui_only.include_globs, ["a[[]1[]].rs"]);
+    assert_eq!(ui_only.selection(&files), (selected.clone(), partials));
+
+    let all = HashMap::from([(files[0].clone(), true), (files[1].clone(), true)]);
+    let everything = Preset::from_selection("everything", &files, &all, &HashMap::new()).unwrap();
+    assert!(everything.include_globs.is_empty());
+    let none = HashMap::from([(files[0].clone(), false)]);
+    assert!(Preset::from_selection("nothing", &files, &none, &HashMap::new()).is_err());
+
+    save_preset(project.root(), ui_only.clone()).unwrap();
+    save_preset(project.root(), everything).unwrap();
+    let updated = Preset {
+        instructions: Some("UI".to_string()),
+        ..ui_only
+    };
+    save_preset(project.root(), updated.clone()).unwrap();
+    let presets = load_presets(project.root()).unwrap();
+    assert_eq!(
+        presets.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+        ["ui-only", "everything"]
+    );
+    assert_eq!(presets[0], updated);
+
+    assert_eq!(delete_preset(project.root(), "everything"), Ok(true));
+    assert_eq!(delete_preset(project.root(), "everything"), Ok(false));
+    assert_eq!(load_presets(project.root()).unwrap().len(), 1);
+    // The preset file never shows up as project content.
+    assert!(scan_files(project.root()).is_empty());
+}
+
+#[test]
+fn a_corrupt_preset_file_is_an_error_and_not_overwritten() {
+    let project = Project::new();
+    project.write(PRESETS_FILE, "{ not json");
+    assert!(load_presets(project.root()).is_err());
+    assert!(
+        save_preset(
+            project.root(),
+            Preset {
+                name: "x".to_string(),
+                ..Preset::default()
+            }
+        )
+        .is_err()
+    );
+    assert_eq!(project.read(PRESETS_FILE), "{ not json");
+}
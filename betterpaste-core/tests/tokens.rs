@@ -0,0 +1,81 @@
+mod common;
+
+use betterpaste_core::{
+    AppConfig, FileTokens, compress_code, context_tokens, count_file_tokens, estimate_tokens,
+    fit_to_budget,
+};
+use common::Project;
+use std::{collections::HashMap, path::PathBuf};
+
+#[test]
+fn estimates_code_like_a_bpe_tokenizer() {
+    assert_eq!(estimate_tokens(""), 0);
+    // "fn", " main", "()", " {", "}" split into symbols as well
+    assert_eq!(estimate_tokens("fn main() {}"), 6);
+    assert_eq!(estimate_tokens("identifier"), 3);
+    assert_eq!(estimate_tokens("1234567"), 3);
+    assert_eq!(estimate_tokens("a\n    b"), 3);
+}
+
+#[test]
+fn counts_full_and_compressed_sizes() {
+    let project = Project::new();
+    let content = "pub fn a() {\n    let value = compute_something_long();\n}\n";
+    project.write("src/lib.rs", content);
+    let files = vec![PathBuf::from("src/lib.rs"), PathBuf::from("missing.rs")];
+
+    let counts = count_file_tokens(project.root(), &files, &HashMap::new());
+    assert_eq!(counts.len(), 1);
+    assert_eq!(
+        counts[&files[0]],
+        FileTokens {
+            full: estimate_tokens(content),
+            compressed: estimate_tokens(&compress_code(&files[0], content)),
+        }
+    );
+}
+
+#[test]
+fn fits_budget_by_compressing_the_largest_files_first() {
+    let files: Vec<PathBuf> = ["big.rs", "medium.rs", "small.rs"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let counts: HashMap<PathBuf, FileTokens> = [(5_000, 500), (2_000, 300), (100, 90)]
+        .into_iter()
+        .zip(&files)
+        .map(|((full, compressed), f)| (f.clone(), FileTokens { full, compressed }))
+        .collect();
+    let selected: HashMap<PathBuf, bool> = files.iter().map(|f| (f.clone(), true)).collect();
+    let mut partials = HashMap::new();
+    let config = AppConfig::default();
+
+    let before = context_tokens(&files, &selected, &partials, &counts, &config);
+    let switched = fit_to_budget(
+        &files,
+        &selected,
+        &mut partials,
+        &counts,
+        &config,
+        before - 4_000,
+    );
+    assert_eq!(switched, [PathBuf::from("big.rs")]);
+    assert!(context_tokens(&files, &selected, &partials, &counts, &config) <= before - 4_000);
+
+    let switched = fit_to_budget(&files, &selected, &mut partials, &counts, &config, 0);
+    assert_eq!(
+        switched,
+        [PathBuf::from("medium.rs"), PathBuf::from("small.rs")]
+    );
+}
+
+#[test]
+fn budget_follows_the_target_model() {
+    let mut config = AppConfig {
+        target_model: "Claude".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(config.token_budget(), Some(200_000));
+    config.target_model = "Unknown".to_string();
+    assert_eq!(config.token_budget(), None);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use betterpaste_core::{AppConfig, PatchOperation, SharedAppState};
+use common::{Project, operation, replace};
+
+fn two_projects() -> (Project, Project, SharedAppState) {
+    let backend = Project::new();
+    backend.write("src/server.rs", "fn serve() {}\n");
+    let frontend = Project::new();
+    frontend.write("web/app.ts", "export {}\n");
+
+    let config = AppConfig::default();
+    let mut state = SharedAppState::new(backend.root().to_path_buf(), &config);
+    state.open(frontend.root().to_path_buf(), &config);
+    (backend, frontend, state)
+}
+
+#[test]
+fn reopening_a_root_reuses_its_workspace() {
+    let (backend, _frontend, mut state) = two_projects();
+    assert_eq!(
+        state.open(backend.root().to_path_buf(), &AppConfig::default()),
+        0
+    );
+    assert_eq!(state.workspaces.len(), 2);
+    assert_ne!(state.workspaces[0].id, state.workspaces[1].id);
+}
+
+#[test]
+fn routes_by_explicit_project_id() {
+    let (_backend, _frontend, state) = two_projects();
+    let id = state.workspaces[1].id.clone();
+    let patch = replace("src/server.rs", "a", "b").data;
+    assert_eq!(state.route(Some(&id), std::slice::from_ref(&patch)), Ok(1));
+    assert!(state.route(Some("nope"), &[patch]).is_err());
+}
+
+#[test]
+fn routes_by_file_path() {
+    let (_backend, _frontend, mut state) = two_projects();
+    let web = replace("web/app.ts", "export {}", "export const x = 1;").data;
+    let server = replace("src/server.rs", "fn serve() {}", "fn serve() -> u8 { 0 }").data;
+    assert_eq!(state.route(None, std::slice::from_ref(&web)), Ok(1));
+    assert_eq!(state.route(None, std::slice::from_ref(&server)), Ok(0));
+
+    let new_web_file = operation(PatchOperation::Create, "web/util.ts", "", None).data;
+    assert_eq!(
+        state.route(None, std::slice::from_ref(&new_web_file)),
+        Ok(1)
+    );
+
+    state.active = 1;
+    let unknown = replace("docs/readme.md", "a", "b").data;
+    assert_eq!(state.route(None, &[unknown]), Ok(1));
+}
+
+#[test]
+fn closing_keeps_the_last_workspace() {
+    let (_backend, _frontend, mut state) = two_projects();
+    state.active = 1;
+    state.close(1);
+    assert_eq!(state.workspaces.len(), 1);
+    assert_eq!(state.active, 0);
+    state.close(0);
+    assert_eq!(state.workspaces.len(), 1);
+}
+
+#[test]
+fn finds_patches_in_any_workspace() {
+    let (_backend, _frontend, mut state) = two_projects();
+    let patch = replace("web/app.ts", "export {}", "");
+    let id = patch.id.clone();
+    state.workspaces[1].patches.push(patch);
+    assert_eq!(state.find_patch(&id), Some((1, 0)));
+    assert_eq!(state.find_patch("nope"), None);
+}
@@ -0,0 +1,1870 @@
+//! The egui front end: Generator, Patcher, Ungenerator, Config and Help tabs.
+
+use crate::{SharedConfigRef, SharedStateRef, load_history, save_config};
+use betterpaste_core::{
+    AppConfig, ContextSelection, ExtractOutcome, FileFilter, FileTokens, Language, MatchTier, ModelBudget, PatchAction,
+    PatchEntry, PatchOperation, PatchStatus, PathSandbox, Preset, SkipReason, Symbol, SymbolKind, SymbolMode,
+    SymbolModes, act_on_patch, apply_group, apply_patch, atomic_write, context_tokens, count_file_tokens,
+    delete_preset, detect_skips, extract_files, fit_to_budget, generate_xml, load_presets,
+    outline_symbols, parse_patch_blocks, push_group, resume_queue, save_preset, scan_files, track_changes,
+    undo_group,
+};
+use eframe::egui;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub fn load_icon() -> egui::IconData {
+    let icon_bytes = include_bytes!("../betterPaste.ico");
+    let image = image::load_from_memory(icon_bytes)
+        .expect("Failed to load icon")
+        .to_rgba8();
+    let (icon_width, icon_height) = image.dimensions();
+    egui::IconData {
+        rgba: image.into_raw(),
+        width: icon_width,
+        height: icon_height,
+    }
+}
+
+pub struct BetterPasteApp {
+    state: SharedStateRef,
+    config: AppConfig,
+    /// What the running server uses; updated whenever `config` is saved.
+    server_config: SharedConfigRef,
+    /// Generator file lists, keyed by workspace id.
+    selections: HashMap<String, FileSelection>,
+    generated_output: String,
+    /// What the last Generate did to fit the token budget, if anything.
+    budget_note: Option<String>,
+    current_tab: AppTab,
+
+    // UI State for Patcher
+    expanded_patch_id: Option<String>,
+    /// Patch count per workspace id at the last frame, to spot new arrivals.
+    last_patch_counts: HashMap<String, usize>,
+    manual_patch_input: String, // For manual pasting
+}
+
+/// The Generator's view of one workspace's files.
+#[derive(Default)]
+struct FileSelection {
+    available_files: Vec<PathBuf>,
+    selected_files: HashMap<PathBuf, bool>,
+    partial_files: HashMap<PathBuf, bool>,
+    /// Per-symbol overrides of the file-level Full/Partial choice.
+    symbol_modes: HashMap<PathBuf, SymbolModes>,
+    tokens: HashMap<PathBuf, FileTokens>,
+    /// Binary, oversized and generated files, which only get selected by hand.
+    skipped: HashMap<PathBuf, SkipReason>,
+    /// Files whose symbol list is open, with their outline read when it was opened.
+    expanded: HashSet<PathBuf>,
+    outlines: HashMap<PathBuf, Vec<Symbol>>,
+    /// The project's saved presets and the one chosen last, if any.
+    presets: Vec<Preset>,
+    preset: Option<String>,
+    preset_error: Option<String>,
+    new_preset_name: String,
+    filter_text: String,
+    filter: FileFilter,
+    filter_error: Option<String>,
+    /// The selection changed since it was last published to the server.
+    unpublished: bool,
+}
+
+/// A folder of the filtered file list. Files are indices into `available_files`.
+#[derive(Default)]
+struct DirNode {
+    dirs: BTreeMap<String, DirNode>,
+    files: Vec<usize>,
+}
+
+impl DirNode {
+    fn build(files: &[PathBuf], filter: &FileFilter) -> Self {
+        let mut root = DirNode::default();
+        for (index, file) in files.iter().enumerate().filter(|(_, f)| filter.matches(f)) {
+            let mut node = &mut root;
+            if let Some(parent) = file.parent() {
+                for part in parent.iter() {
+                    node = node.dirs.entry(part.to_string_lossy().into_owned()).or_default();
+                }
+            }
+            node.files.push(index);
+        }
+        root
+    }
+
+    /// Every file in this folder and below.
+    fn all_files(&self) -> Vec<usize> {
+        let mut files = self.files.clone();
+        for dir in self.dirs.values() {
+            files.extend(dir.all_files());
+        }
+        files
+    }
+}
+
+impl FileSelection {
+    fn is_selected(&self, file: &Path) -> bool {
+        *self.selected_files.get(file).unwrap_or(&false)
+    }
+
+    fn is_partial(&self, file: &Path) -> bool {
+        *self.partial_files.get(file).unwrap_or(&false)
+    }
+
+    /// Tokens the file adds to the context as it is currently marked.
+    fn file_tokens(&self, file: &Path) -> usize {
+        self.tokens.get(file).map_or(0, |count| {
+            if self.is_partial(file) { count.compressed } else { count.full }
+        })
+    }
+
+    /// The filter box and the bulk actions on the files it matches.
+    fn filter_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.filter_text)
+                    .hint_text("src/**/*.rs, re:_test\\.py$ or text")
+                    .desired_width(220.0),
+            );
+            if response.changed() {
+                match FileFilter::parse(&self.filter_text) {
+                    Ok(filter) => {
+                        self.filter = filter;
+                        self.filter_error = None;
+                    }
+                    Err(e) => self.filter_error = Some(e),
+                }
+            }
+            if let Some(error) = &self.filter_error {
+                ui.colored_label(egui::Color32::RED, "Invalid filter").on_hover_text(error);
+            }
+        });
+        let matching: Vec<PathBuf> = self
+            .available_files
+            .iter()
+            .filter(|f| self.filter.matches(f))
+            .cloned()
+            .collect();
+        ui.horizontal(|ui| {
+            ui.label(format!("{} of {} files:", matching.len(), self.available_files.len()));
+            let apply = |flags: &mut HashMap<PathBuf, bool>, on: bool| {
+                for file in &matching {
+                    flags.insert(file.clone(), on);
+                }
+            };
+            if ui.button("Select").clicked() {
+                apply(&mut self.selected_files, true);
+                self.unpublished = true;
+            }
+            if ui.button("Deselect").clicked() {
+                apply(&mut self.selected_files, false);
+                self.unpublished = true;
+            }
+            if ui.button("Partial").clicked() {
+                apply(&mut self.partial_files, true);
+                self.unpublished = true;
+            }
+            if ui.button("Full").clicked() {
+                apply(&mut self.partial_files, false);
+                self.unpublished = true;
+            }
+        });
+    }
+
+    /// Draws the folders of `node` as collapsible rows with a tri-state checkbox, counts and
+    /// tokens, followed by its files. Folders start open while a filter is active.
+    fn dir_rows(&mut self, ui: &mut egui::Ui, node: &DirNode, path: &str, filtering: bool, root: &Path) {
+        for (name, dir) in &node.dirs {
+            let dir_path = format!("{path}{name}/");
+            let files: Vec<PathBuf> = dir.all_files().into_iter().map(|i| self.available_files[i].clone()).collect();
+            let selected: Vec<&PathBuf> = files.iter().filter(|f| self.is_selected(f)).collect();
+            let tokens: usize = selected.iter().map(|f| self.file_tokens(f)).sum();
+            let id = ui.make_persistent_id(("dir", filtering, &dir_path));
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, filtering)
+                .show_header(ui, |ui| {
+                    let mut all = selected.len() == files.len();
+                    let partly = !selected.is_empty() && !all;
+                    let checkbox = egui::Checkbox::new(&mut all, format!("📁 {name}")).indeterminate(partly);
+                    if ui.add(checkbox).changed() {
+                        for file in &files {
+                            self.selected_files.insert(file.clone(), all);
+                        }
+                        self.unpublished = true;
+                    }
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{}/{} files, ~{}",
+                            selected.len(),
+                            files.len(),
+                            format_tokens(tokens)
+                        ))
+                        .weak(),
+                    );
+                })
+                .body(|ui| self.dir_rows(ui, dir, &dir_path, filtering, root));
+        }
+        for &index in &node.files {
+            let file = self.available_files[index].clone();
+            self.file_row(ui, &file, root);
+        }
+    }
+
+    /// One file with its selected and Partial checkboxes, its tokens and, when expanded,
+    /// its symbols.
+    fn file_row(&mut self, ui: &mut egui::Ui, file: &PathBuf, root: &Path) {
+        let mut is_sel = self.is_selected(file);
+        let is_expanded = is_sel && self.expanded.contains(file);
+        ui.horizontal(|ui| {
+            let toggle = if is_expanded { "▾" } else { "▸" };
+            if ui
+                .add_enabled(
+                    is_sel && Language::from_path(file).is_some(),
+                    egui::Button::new(toggle).frame(false),
+                )
+                .on_hover_text("Choose which functions and types to include")
+                .clicked()
+            {
+                if is_expanded {
+                    self.expanded.remove(file);
+                } else {
+                    self.expanded.insert(file.clone());
+                }
+            }
+            let name = file.file_name().map_or_else(|| file.to_string_lossy(), |n| n.to_string_lossy());
+            if ui.checkbox(&mut is_sel, name).on_hover_text(file.to_string_lossy()).changed() {
+                self.selected_files.insert(file.clone(), is_sel);
+                self.unpublished = true;
+            }
+            if is_sel {
+                let mut is_part = self.is_partial(file);
+                if ui.checkbox(&mut is_part, "Partial").changed() {
+                    self.partial_files.insert(file.clone(), is_part);
+                    self.unpublished = true;
+                }
+            }
+            if let Some(count) = self.tokens.get(file) {
+                let n = if is_sel && self.is_partial(file) { count.compressed } else { count.full };
+                ui.label(egui::RichText::new(format!("~{}", format_tokens(n))).weak());
+            }
+            if let Some(reason) = self.skipped.get(file) {
+                ui.colored_label(egui::Color32::from_rgb(230, 160, 60), format!("⚠ {}", reason))
+                    .on_hover_text("Left out by default; tick the file to include it anyway");
+            }
+        });
+        if !is_expanded {
+            return;
+        }
+        let inherited = if self.is_partial(file) { SymbolMode::Signature } else { SymbolMode::Full };
+        let outline = self.outlines.entry(file.clone()).or_insert_with(|| {
+            fs::read_to_string(root.join(file))
+                .map(|content| outline_symbols(file, &content))
+                .unwrap_or_default()
+        });
+        let modes = self.symbol_modes.entry(file.clone()).or_default();
+        let mut changed = false;
+        ui.indent(file, |ui| {
+            if outline.is_empty() {
+                ui.label(egui::RichText::new("No functions or types found").weak());
+            }
+            changed = symbol_rows(ui, outline, modes, inherited);
+            if !modes.is_empty() && ui.small_button("Reset symbols").clicked() {
+                modes.clear();
+                changed = true;
+            }
+        });
+        if changed {
+            self.tokens.extend(count_file_tokens(root, std::slice::from_ref(file), &self.symbol_modes));
+            self.unpublished = true;
+        }
+    }
+
+    fn active_preset(&self) -> Option<&Preset> {
+        let name = self.preset.as_ref()?;
+        self.presets.iter().find(|p| &p.name == name)
+    }
+
+    fn reload_presets(&mut self, root: &Path) {
+        match load_presets(root) {
+            Ok(presets) => {
+                self.presets = presets;
+                self.preset_error = None;
+            }
+            Err(e) => self.preset_error = Some(e),
+        }
+        if self.preset.is_some() && self.active_preset().is_none() {
+            self.preset = None;
+            self.unpublished = true;
+        }
+    }
+
+    /// Copies the selection into `published` (the workspace's shared copy, read by the
+    /// server) if it changed since the last time, or nothing was published yet.
+    fn publish(&mut self, published: &mut Option<ContextSelection>) {
+        if !std::mem::take(&mut self.unpublished) && published.is_some() {
+            return;
+        }
+        *published = Some(ContextSelection {
+            selected: self.selected_files.clone(),
+            partials: self.partial_files.clone(),
+            symbols: self.symbol_modes.clone(),
+            preset: self.preset.clone(),
+        });
+    }
+}
+
+#[derive(PartialEq)]
+enum AppTab {
+    Generator,
+    Patcher,
+    Ungenerator,
+    Configuration,
+    About,
+    Help,
+}
+
+impl BetterPasteApp {
+    pub fn new(
+        _cc: &eframe::CreationContext,
+        state: SharedStateRef,
+        config: AppConfig,
+        server_config: SharedConfigRef,
+    ) -> Self {
+        let last_patch_counts = state
+            .lock()
+            .workspaces
+            .iter()
+            .map(|w| (w.id.clone(), w.patches.len()))
+            .collect();
+        let mut app = Self {
+            state,
+            config,
+            server_config,
+            selections: HashMap::new(),
+            generated_output: String::new(),
+            budget_note: None,
+            current_tab: AppTab::Generator,
+            expanded_patch_id: None,
+            last_patch_counts,
+            manual_patch_input: String::new(),
+        };
+        app.rescan();
+        app
+    }
+
+    /// Rescans the active workspace. A first scan selects every file that is not skipped.
+    fn rescan(&mut self) {
+        let (id, root) = {
+            let state = self.state.lock();
+            (state.active().id.clone(), state.active().sandbox.root.clone())
+        };
+        let selection = self.selections.entry(id).or_default();
+        selection.available_files = scan_files(&root);
+        selection.skipped = detect_skips(&root, &selection.available_files, &self.config);
+        // Unreadable and oversized files are not worth reading just for an estimate.
+        let readable: Vec<PathBuf> = selection
+            .available_files
+            .iter()
+            .filter(|f| !matches!(selection.skipped.get(*f), Some(SkipReason::Binary | SkipReason::TooLarge { .. })))
+            .cloned()
+            .collect();
+        selection.tokens = count_file_tokens(&root, &readable, &selection.symbol_modes);
+        selection.outlines.clear();
+        selection.reload_presets(&root);
+        if selection.selected_files.is_empty() {
+            for f in &selection.available_files {
+                selection.selected_files.insert(f.clone(), !selection.skipped.contains_key(f));
+            }
+            selection.unpublished = true;
+        }
+    }
+
+    /// Opens `root` as a workspace with its patch history, or switches to it if it is
+    /// already open.
+    fn open_project(&mut self, root: PathBuf) {
+        {
+            let mut state = self.state.lock();
+            let open = state.workspaces.len();
+            let index = state.open(root.clone(), &self.config);
+            if index == open {
+                let workspace = &mut state.workspaces[index];
+                load_history(workspace, &self.config);
+                self.last_patch_counts.insert(workspace.id.clone(), workspace.patches.len());
+            }
+            state.active = index;
+        }
+        self.rescan();
+
+        self.config.remember_project(&root);
+        self.save_open_projects();
+    }
+
+    fn close_workspace(&mut self, index: usize) {
+        {
+            let mut state = self.state.lock();
+            if let Some(workspace) = state.workspaces.get(index) {
+                self.selections.remove(&workspace.id);
+            }
+            state.close(index);
+        }
+        self.rescan();
+        self.save_open_projects();
+    }
+
+    fn save_open_projects(&mut self) {
+        self.config.open_projects = self
+            .state
+            .lock()
+            .workspaces
+            .iter()
+            .map(|w| w.sandbox.root.clone())
+            .collect();
+        persist_config(&self.config, &self.server_config);
+    }
+
+    /// One tab per open workspace, plus opening and closing projects.
+    fn ui_workspace_bar(&mut self, ui: &mut egui::Ui) {
+        let (tabs, active, root) = {
+            let state = self.state.lock();
+            let tabs: Vec<(String, bool)> = state
+                .workspaces
+                .iter()
+                .map(|w| (w.id.clone(), w.new_patch_alert))
+                .collect();
+            (tabs, state.active, state.active().sandbox.root.clone())
+        };
+        let mut open = None;
+        let mut close = None;
+        let mut select = None;
+        ui.horizontal(|ui| {
+            ui.label("Projects:");
+            for (i, (id, alert)) in tabs.iter().enumerate() {
+                let label = if *alert { format!("🔴 {}", id) } else { id.clone() };
+                if ui.selectable_label(i == active, label).clicked() {
+                    select = Some(i);
+                }
+            }
+            if tabs.len() > 1 && ui.small_button("✖").on_hover_text("Close this project").clicked() {
+                close = Some(active);
+            }
+            ui.separator();
+            if ui.button("📂 Open Project...").clicked() {
+                open = rfd::FileDialog::new().set_directory(&root).pick_folder();
+            }
+            egui::ComboBox::from_id_salt("recent_projects")
+                .selected_text("Recent")
+                .show_ui(ui, |ui| {
+                    for recent in &self.config.recent_projects {
+                        let exists = recent.is_dir();
+                        if ui
+                            .add_enabled(exists, egui::Button::new(recent.display().to_string()))
+                            .clicked()
+                        {
+                            open = Some(recent.clone());
+                        }
+                    }
+                });
+            ui.separator();
+            ui.monospace(root.display().to_string());
+        });
+        if let Some(index) = select {
+            let mut state = self.state.lock();
+            state.active = index;
+            if self.current_tab == AppTab::Patcher {
+                state.active_mut().new_patch_alert = false;
+            }
+        }
+        if let Some(index) = close {
+            self.close_workspace(index);
+        }
+        if let Some(root) = open {
+            self.open_project(root);
+        }
+        let active_id = self.state.lock().active().id.clone();
+        if !self.selections.contains_key(&active_id) {
+            self.rescan();
+        }
+    }
+
+    fn unpause_queue(&self) {
+        resume_queue(self.state.lock().active_mut());
+    }
+}
+
+impl eframe::App for BetterPasteApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        ctx.request_repaint_after(Duration::from_millis(500));
+
+        {
+            let state = self.state.lock();
+            for workspace in &state.workspaces {
+                let count = self.last_patch_counts.entry(workspace.id.clone()).or_default();
+                if workspace.patches.len() > *count {
+                    if let Some(last) = workspace.patches.last() {
+                        self.expanded_patch_id = Some(last.id.clone());
+                    }
+                }
+                *count = workspace.patches.len();
+            }
+        }
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("BetterPaste");
+                ui.separator();
+                if ui
+                    .selectable_label(self.current_tab == AppTab::Generator, "Generator")
+                    .clicked()
+                {
+                    self.current_tab = AppTab::Generator;
+                }
+                let alert = { self.state.lock().workspaces.iter().any(|w| w.new_patch_alert) };
+                let btn_text = if alert { "🔴 Patcher" } else { "Patcher" };
+                if ui
+                    .selectable_label(self.current_tab == AppTab::Patcher, btn_text)
+                    .clicked()
+                {
+                    self.current_tab = AppTab::Patcher;
+                    self.state.lock().active_mut().new_patch_alert = false;
+                }
+                if ui
+                    .selectable_label(self.current_tab == AppTab::Ungenerator, "Ungenerator")
+                    .clicked()
+                {
+                    self.current_tab = AppTab::Ungenerator;
+                }
+
+                ui.separator();
+
+                if ui
+                    .selectable_label(self.current_tab == AppTab::Configuration, "Configuration")
+                    .clicked()
+                {
+                    self.current_tab = AppTab::Configuration;
+                }
+                if ui
+                    .selectable_label(self.current_tab == AppTab::Help, "Help")
+                    .clicked()
+                {
+                    self.current_tab = AppTab::Help;
+                }
+                if ui
+                    .selectable_label(self.current_tab == AppTab::About, "About")
+                    .clicked()
+                {
+                    self.current_tab = AppTab::About;
+                }
+            });
+            self.ui_workspace_bar(ui);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| match self.current_tab {
+            AppTab::Generator => self.ui_generator(ui),
+            AppTab::Patcher => self.ui_patcher(ui),
+            AppTab::Ungenerator => self.ui_ungenerator(ui),
+            AppTab::Configuration => self.ui_config(ui),
+            AppTab::About => self.ui_about(ui),
+            AppTab::Help => self.ui_help(ui),
+        });
+    }
+}
+
+impl BetterPasteApp {
+    fn ui_about(&mut self, ui: &mut egui::Ui) {
+        ui.heading("About BetterPaste");
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.config.about_content)
+                    .desired_width(f32::INFINITY)
+                    .frame(false)
+                    .interactive(false),
+            );
+        });
+    }
+
+    fn ui_ungenerator(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Extract (Safe Mode)").clicked() {
+                let state = self.state.lock();
+                for (name, outcome) in extract_files(&self.generated_output, &state.active().sandbox, false) {
+                    match outcome {
+                        ExtractOutcome::Rejected(e) => eprintln!("Skipping extraction: {}", e),
+                        ExtractOutcome::Failed(e) => eprintln!("Failed to extract {}: {}", name, e),
+                        _ => {}
+                    }
+                }
+            }
+            ui.label("Files marked in RED already exist or are outside the project and are skipped.");
+        });
+
+        ui.separator();
+
+        ui.columns(2, |columns| {
+            // Left: Preview / Analysis
+            columns[0].vertical(|ui| {
+                ui.heading("Preview Analysis");
+                egui::ScrollArea::vertical()
+                    .id_salt("ungenerator_preview")
+                    .show(ui, |ui| {
+                        let re = regex::Regex::new(
+                            r#"(?ms)<File path="([^"]+)"(?: compressed="[^"]+")?>"#,
+                        )
+                        .unwrap();
+                        let state = self.state.lock();
+                        for caps in re.captures_iter(&self.generated_output) {
+                            if let Some(path_match) = caps.get(1) {
+                                let path = PathBuf::from(path_match.as_str());
+                                let resolved = state.active().sandbox.resolve(path_match.as_str());
+                                let exists = resolved.as_ref().is_ok_and(|p| p.exists());
+                                ui.horizontal(|ui| {
+                                    if let Err(reason) = &resolved {
+                                        ui.colored_label(
+                                            egui::Color32::RED,
+                                            format!("REJECTED: {}", reason),
+                                        );
+                                    } else if exists {
+                                        ui.colored_label(
+                                            egui::Color32::RED,
+                                            format!("EXISTS: {}", path.display()),
+                                        );
+                                    } else {
+                                        ui.colored_label(
+                                            egui::Color32::GREEN,
+                                            format!("NEW: {}", path.display()),
+                                        );
+                                    }
+                                });
+                            }
+                        }
+                    });
+            });
+
+            // Right: Input
+            columns[1].vertical(|ui| {
+                ui.heading("XML Input");
+                egui::ScrollArea::vertical()
+                    .id_salt("ungenerator_input")
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.generated_output)
+                                .code_editor()
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(30),
+                        );
+                    });
+            });
+        });
+    }
+    fn ui_config(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Configuration");
+        ui.add_space(10.0);
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.group(|ui| {
+                ui.label("Server Port (Default: 3030):");
+                ui.add(egui::DragValue::new(&mut self.config.port).range(1024..=65535));
+                ui.add_space(5.0);
+                ui.label("Allowed Origins (one per line; pages that may call the server):");
+                let mut origins = self.config.allowed_origins.join("\n");
+                if ui
+                    .add(egui::TextEdit::multiline(&mut origins).code_editor().desired_rows(4))
+                    .changed()
+                {
+                    self.config.allowed_origins = origins.split('\n').map(String::from).collect();
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Regenerate API Token")
+                        .on_hover_text("Takes effect at once; the userscript must be updated with the new token from the Help tab")
+                        .clicked()
+                    {
+                        self.config.api_token.clear();
+                        self.config.ensure_api_token();
+                        // Saved at once, so the server accepts the token the Help tab shows.
+                        persist_config(&self.config, &self.server_config);
+                    }
+                });
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Overview Instructions:");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.config.instructions)
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Replacing Rules Instructions:");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.config.replacing_rules)
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Example Block (Sent to AI):");
+                ui.label(
+                    egui::RichText::new(
+                        "This shows the AI how to format the response. Note the outer code fences.",
+                    )
+                    .size(10.0)
+                    .weak(),
+                );
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.config.example)
+                        .code_editor()
+                        .desired_rows(12)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Patch Journal (.betterpaste/journal):");
+                ui.horizontal(|ui| {
+                    ui.label("Keep days:");
+                    ui.add(egui::DragValue::new(&mut self.config.journal_max_age_days).range(1..=3650));
+                    ui.label("Max size (MB):");
+                    ui.add(egui::DragValue::new(&mut self.config.journal_max_size_mb).range(1..=10240));
+                });
+                ui.label(
+                    egui::RichText::new("Older entries are pruned on startup.")
+                        .size(10.0)
+                        .weak(),
+                );
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Patch Target Rules (one glob per line, relative to the project root):");
+                ui.label(
+                    egui::RichText::new(
+                        "Patches may only touch files matching Allow and never files matching Deny. Paths outside the project or in .git/ are always rejected.",
+                    )
+                    .size(10.0)
+                    .weak(),
+                );
+                ui.columns(2, |cols| {
+                    cols[0].label("Allow:");
+                    let mut allow = self.config.patch_allow_globs.join("\n");
+                    if cols[0]
+                        .add(egui::TextEdit::multiline(&mut allow).code_editor().desired_rows(4))
+                        .changed()
+                    {
+                        self.config.patch_allow_globs = allow.split('\n').map(String::from).collect();
+                    }
+                    cols[1].label("Deny:");
+                    let mut deny = self.config.patch_deny_globs.join("\n");
+                    if cols[1]
+                        .add(egui::TextEdit::multiline(&mut deny).code_editor().desired_rows(4))
+                        .changed()
+                    {
+                        self.config.patch_deny_globs = deny.split('\n').map(String::from).collect();
+                    }
+                });
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Skipped Files (left out of contexts unless selected by hand):");
+                ui.horizontal(|ui| {
+                    ui.label("Max file size (KB, 0 = no limit):");
+                    ui.add(egui::DragValue::new(&mut self.config.max_file_size_kb).range(0..=1_048_576));
+                });
+                ui.label("Generated and vendored files:");
+                let mut generated = self.config.generated_globs.join("\n");
+                if ui
+                    .add(egui::TextEdit::multiline(&mut generated).code_editor().desired_rows(4))
+                    .changed()
+                {
+                    self.config.generated_globs = generated.split('\n').map(String::from).collect();
+                }
+                ui.label(
+                    egui::RichText::new(
+                        "Binary and minified files, and files marked linguist-generated or linguist-vendored in .gitattributes, are skipped too.",
+                    )
+                    .size(10.0)
+                    .weak(),
+                );
+            });
+
+            ui.add_space(10.0);
+
+            ui.group(|ui| {
+                ui.label("Model Token Budgets (context window per target model):");
+                let mut remove = None;
+                for (i, budget) in self.config.model_budgets.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut budget.model).desired_width(150.0));
+                        ui.add(egui::DragValue::new(&mut budget.max_tokens).range(1_000..=10_000_000).speed(1_000));
+                        ui.label("tokens");
+                        if ui.small_button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.config.model_budgets.remove(i);
+                }
+                if ui.button("➕ Add Model").clicked() {
+                    self.config.model_budgets.push(ModelBudget {
+                        model: "New Model".to_string(),
+                        max_tokens: 128_000,
+                    });
+                }
+            });
+
+            ui.add_space(15.0);
+
+            if ui.button("💾 Save Configuration").clicked() {
+                persist_config(&self.config, &self.server_config);
+                for workspace in self.state.lock().workspaces.iter_mut() {
+                    let root = workspace.sandbox.root.clone();
+                    workspace.sandbox = PathSandbox::new(root, &self.config);
+                }
+                self.rescan();
+            }
+        });
+    }
+
+    fn ui_help(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Help & Setup");
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.label(egui::RichText::new("1. Browser Setup").strong().size(16.0));
+            ui.label("To allow the AI to communicate with BetterPaste, you need a userscript manager.");
+            ui.label("Recommended: Tampermonkey or Violentmonkey.");
+            ui.label("1. Install the extension for Chrome/Firefox.");
+            ui.label("2. Create a new script.");
+            ui.label("3. Paste the code below and save.");
+            ui.label("4. When prompted, allow the script to access '127.0.0.1'.");
+            ui.label("The copied script already contains this install's API token. If you pasted the script before, set API_TOKEN in it to:");
+            ui.horizontal(|ui| {
+                ui.monospace(&self.config.api_token);
+                if ui.button("Copy Token").clicked() {
+                    if let Ok(mut cb) = arboard::Clipboard::new() { let _ = cb.set_text(self.config.api_token.clone()); }
+                }
+            });
+
+            ui.add_space(5.0);
+
+            let script = userscript(&self.config);
+            ui.collapsing("Show Userscript", |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Copy Script to Clipboard").clicked() {
+                        if let Ok(mut cb) = arboard::Clipboard::new() { let _ = cb.set_text(script.clone()); }
+                    }
+                });
+
+                let mut script_display = script.clone();
+                ui.add(egui::TextEdit::multiline(&mut script_display).code_editor().desired_width(f32::INFINITY).desired_rows(15).interactive(false));
+            });
+
+            ui.add_space(20.0);
+
+            ui.label(egui::RichText::new("2. Workflow").strong().size(16.0));
+            ui.label("1. Pick your project folder with 'Open Project...' (or start with --root <folder>), then go to the 'Generator' tab.");
+            ui.label("2. Select files to include (use 'Partial' for large files to hide function bodies; Rust, Python, TypeScript/JavaScript, Go, C# and other C-style languages are supported).");
+            ui.label("3. Click 'Generate XML' and copy to clipboard.");
+            ui.label("4. Paste into your AI chat.");
+            ui.label("5. When the AI responds with code blocks, they will appear in the 'Patcher' tab.");
+            ui.label("6. Review and Apply changes.");
+
+            ui.add_space(20.0);
+
+            ui.label(egui::RichText::new("3. The Ungenerator").strong().size(16.0));
+            ui.label("Paste a context XML file into the right panel to unpack it into files.");
+            ui.label("Useful for bootstrapping projects from AI generated XML.");
+            ui.label("Files marked in RED already exist or point outside the project and will be skipped.");
+
+            ui.add_space(20.0);
+
+            ui.label(egui::RichText::new("4. Configuration").strong().size(16.0));
+            ui.label(format!("Changes are saved to '{}' automatically.", crate::config_path().display()));
+            ui.label("Note: Restart is required for Server Port changes to take effect. Allowed Origins apply once saved.");
+        });
+    }
+
+    fn ui_generator(&mut self, ui: &mut egui::Ui) {
+        let (mut rescan, mut generate) = (false, false);
+        ui.horizontal(|ui| {
+            rescan = ui.button("Rescan Project").clicked();
+            generate = ui.button("Generate XML").clicked();
+        });
+        if rescan {
+            self.rescan();
+        }
+        let (id, root) = {
+            let state = self.state.lock();
+            (state.active().id.clone(), state.active().sandbox.root.clone())
+        };
+        let selection = self.selections.entry(id.clone()).or_default();
+        preset_bar(ui, selection, &root, &self.config);
+        // A preset's instructions and rules stand in for the configured ones.
+        let preset_config = selection.active_preset().map(|p| p.apply_to(&self.config));
+        let context_config = preset_config.as_ref().unwrap_or(&self.config);
+        let budget = self.config.token_budget();
+        if generate {
+            self.budget_note = None;
+            if let (Some(budget), true) = (budget, self.config.auto_partial) {
+                let switched = fit_to_budget(
+                    &selection.available_files,
+                    &selection.selected_files,
+                    &mut selection.partial_files,
+                    &selection.tokens,
+                    context_config,
+                    budget,
+                );
+                if !switched.is_empty() {
+                    selection.unpublished = true;
+                    self.budget_note = Some(format!(
+                        "Switched {} largest file(s) to Partial to fit the budget.",
+                        switched.len()
+                    ));
+                }
+            }
+            self.generated_output = generate_xml(
+                &root,
+                &selection.available_files,
+                &selection.selected_files,
+                &selection.partial_files,
+                &selection.symbol_modes,
+                context_config,
+            );
+        }
+
+        let total = context_tokens(
+            &selection.available_files,
+            &selection.selected_files,
+            &selection.partial_files,
+            &selection.tokens,
+            context_config,
+        );
+        let mut config_changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Target model:");
+            egui::ComboBox::from_id_salt("target_model")
+                .selected_text(&self.config.target_model)
+                .show_ui(ui, |ui| {
+                    for budget in &self.config.model_budgets {
+                        config_changed |= ui
+                            .selectable_value(&mut self.config.target_model, budget.model.clone(), &budget.model)
+                            .changed();
+                    }
+                });
+            match budget {
+                Some(budget) if total > budget => {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!("~{} / {} tokens (over by {})", format_tokens(total), format_tokens(budget), format_tokens(total - budget)),
+                    );
+                }
+                Some(budget) => {
+                    ui.label(format!("~{} / {} tokens", format_tokens(total), format_tokens(budget)));
+                }
+                None => {
+                    ui.label(format!("~{} tokens", format_tokens(total)));
+                }
+            }
+            config_changed |= ui
+                .checkbox(&mut self.config.auto_partial, "Auto-Partial over budget")
+                .on_hover_text("On Generate, marks the largest files Partial until the context fits")
+                .changed();
+        });
+        if let Some(note) = &self.budget_note {
+            ui.label(egui::RichText::new(note).weak());
+        } else if budget.is_some_and(|b| total > b) && !self.config.auto_partial {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "The context is larger than the model's window and may be truncated. Mark large files Partial or enable Auto-Partial.",
+            );
+        }
+        if config_changed {
+            persist_config(&self.config, &self.server_config);
+        }
+
+        ui.separator();
+        ui.columns(2, |columns| {
+            columns[0].vertical(|ui| {
+                ui.heading("Files");
+                selection.filter_bar(ui);
+                let tree = DirNode::build(&selection.available_files, &selection.filter);
+                let filtering = !selection.filter.is_all();
+                egui::ScrollArea::vertical()
+                    .id_salt("file_list")
+                    .show(ui, |ui| {
+                        selection.dir_rows(ui, &tree, "", filtering, &root);
+                    });
+            });
+            columns[1].vertical(|ui| {
+                ui.heading("Context Output");
+                ui.horizontal(|ui| {
+                    if ui.button("Copy to Clipboard (Formatted)").clicked() {
+                        // Wraps in xml code block to preserve whitespace in AI
+                        let formatted = format!("```xml\n{}\n```", self.generated_output);
+                        if let Ok(mut cb) = arboard::Clipboard::new() {
+                            let _ = cb.set_text(formatted);
+                        }
+                    }
+
+                    if ui.button("Save to File...").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .set_file_name("context.xml")
+                            .save_file()
+                        {
+                            if let Err(e) = atomic_write(&path, &self.generated_output) {
+                                eprintln!("Failed to save context: {}", e);
+                            }
+                        }
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .id_salt("output_text")
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.generated_output)
+                                .code_editor()
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(30),
+                        );
+                    });
+            });
+        });
+
+        if let Some(workspace) = self.state.lock().workspaces.iter_mut().find(|w| w.id == id) {
+            selection.publish(&mut workspace.selection);
+        }
+    }
+
+    fn ui_patcher(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Manual Patch Input", |ui| {
+            ui.label("Paste [<(x{START}x)>], CREATE, DELETE or RENAME blocks, or a unified diff (--- a/ +++ b/ @@), here if the script misses them.");
+            ui.add(egui::TextEdit::multiline(&mut self.manual_patch_input).code_editor().desired_rows(3).desired_width(f32::INFINITY));
+            if ui.button("Process Manual Input").clicked() {
+                // Send to local server virtually
+                let patches = parse_patch_blocks(&self.manual_patch_input);
+                if !patches.is_empty() {
+                    let mut guard = self.state.lock();
+                    let state = guard.active_mut();
+                    if patches.len() > 1 {
+                        if let Some(group_id) = push_group(state, patches) {
+                            if !state.is_paused { apply_group(state, &group_id); }
+                        }
+                    } else {
+                        track_changes(state, |state| {
+                            for patch in patches {
+                                let mut entry = PatchEntry::new(patch);
+                                if !state.is_paused { apply_patch(&mut entry, &state.sandbox); }
+                                else { entry.status = PatchStatus::Queued; }
+                                state.patches.push(entry);
+                            }
+                        });
+                    }
+                    self.manual_patch_input.clear();
+                }
+            }
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.heading("Incoming Patches");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                // Auto Dismiss Switch
+                let mut auto_dismiss = { self.state.lock().active().auto_dismiss };
+                if ui
+                    .checkbox(&mut auto_dismiss, "Auto-Dismiss (Reload Protection)")
+                    .changed()
+                {
+                    track_changes(self.state.lock().active_mut(), |w| w.auto_dismiss = auto_dismiss);
+                }
+
+                ui.separator();
+
+                let mut is_paused = { self.state.lock().active().is_paused };
+                let pause_text = if is_paused {
+                    "▶ Resume & Apply Queue"
+                } else {
+                    "⏸ Pause"
+                };
+                if ui.toggle_value(&mut is_paused, pause_text).changed() {
+                    if !is_paused {
+                        self.unpause_queue();
+                    } else {
+                        track_changes(self.state.lock().active_mut(), |w| w.is_paused = true);
+                    }
+                }
+
+                ui.separator();
+
+                let mut auto_apply = { self.state.lock().active().auto_apply };
+                if ui.checkbox(&mut auto_apply, "Auto-Apply").changed() {
+                    track_changes(self.state.lock().active_mut(), |w| w.auto_apply = auto_apply);
+                }
+            });
+        });
+        ui.separator();
+
+        let mut guard = self.state.lock();
+        let state = guard.active_mut();
+        let mut action = None;
+
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+
+                let mut runs: Vec<(Option<String>, std::ops::Range<usize>)> = Vec::new();
+                for (i, patch) in state.patches.iter().enumerate() {
+                    match runs.last_mut() {
+                        Some((group, range)) if group.is_some() && *group == patch.group_id => range.end = i + 1,
+                        _ => runs.push((patch.group_id.clone(), i..i + 1)),
+                    }
+                }
+
+                for (group_id, range) in runs {
+                    let Some(group_id) = group_id else {
+                        for i in range {
+                            let can_dismiss = state.patches[i].status != PatchStatus::Success;
+                            patch_entry_ui(ui, i, &mut state.patches[i], can_dismiss, &mut self.expanded_patch_id, &mut action);
+                        }
+                        continue;
+                    };
+                    let group = state.groups.iter().find(|g| g.id == group_id);
+                    let blocks = &state.patches[range.clone()];
+                    let total = blocks.len();
+                    let applied = blocks.iter().filter(|p| p.status == PatchStatus::Success).count();
+                    let queued = blocks.iter().any(|p| p.status == PatchStatus::Queued);
+                    let header = format!(
+                        "AI Response · {} blocks · {}/{} applied{}",
+                        total,
+                        applied,
+                        total,
+                        group.map(|g| format!(" · {}", g.timestamp)).unwrap_or_default()
+                    );
+                    let error = group.and_then(|g| g.error.clone());
+
+                    ui.group(|ui| {
+                        egui::CollapsingHeader::new(header)
+                            .id_salt(&group_id)
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    if applied < total {
+                                        let label = if queued { "Apply All Now" } else { "Apply All" };
+                                        if ui.button(label).on_hover_text("Applies every block, or rolls all of them back if one fails").clicked() {
+                                            action = Some(PatchListAction::ApplyGroup(group_id.clone()));
+                                        }
+                                    }
+                                    if applied > 0 && ui.button("Undo All").clicked() {
+                                        action = Some(PatchListAction::UndoGroup(group_id.clone()));
+                                    }
+                                    if ui
+                                        .add_enabled(applied == 0, egui::Button::new("✖ Dismiss All"))
+                                        .on_disabled_hover_text("Undo the applied blocks before dismissing")
+                                        .clicked()
+                                    {
+                                        action = Some(PatchListAction::Act(range.start, PatchAction::Dismiss));
+                                    }
+                                });
+                                if let Some(error) = &error {
+                                    ui.colored_label(egui::Color32::RED, error);
+                                }
+                                for i in range.clone() {
+                                    patch_entry_ui(ui, i, &mut state.patches[i], applied == 0, &mut self.expanded_patch_id, &mut action);
+                                }
+                            });
+                    });
+                    ui.add_space(2.0);
+                }
+                let Some(action) = action else {
+                    return;
+                };
+                track_changes(state, |state| match action {
+                    PatchListAction::Act(i, action) => {
+                        // Failures show on the patch or its group; an undo conflict opens the diff.
+                        let _ = act_on_patch(state, i, action);
+                        if state.patches[i].undo_conflict.is_some() {
+                            self.expanded_patch_id = Some(state.patches[i].id.clone());
+                        }
+                    }
+                    PatchListAction::ApplyGroup(group_id) => apply_group(state, &group_id),
+                    PatchListAction::UndoGroup(group_id) => undo_group(state, &group_id),
+                });
+            });
+    }
+}
+
+/// Choosing, saving and deleting the project's presets, and editing the chosen preset's
+/// instructions and rules.
+fn preset_bar(ui: &mut egui::Ui, selection: &mut FileSelection, root: &Path, config: &AppConfig) {
+    let mut chosen = None;
+    let mut save = None;
+    let mut delete = false;
+    ui.horizontal(|ui| {
+        ui.label("Preset:");
+        egui::ComboBox::from_id_salt("preset")
+            .selected_text(selection.preset.as_deref().unwrap_or("(none)"))
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(selection.preset.is_none(), "(none)").clicked() {
+                    selection.preset = None;
+                    selection.unpublished = true;
+                }
+                for preset in &selection.presets {
+                    let is_active = selection.preset.as_ref() == Some(&preset.name);
+                    if ui.selectable_label(is_active, &preset.name).clicked() {
+                        chosen = Some(preset.clone());
+                    }
+                }
+            });
+        if selection.preset.is_some() && ui.button("Delete").clicked() {
+            delete = true;
+        }
+        ui.separator();
+        ui.add(
+            egui::TextEdit::singleline(&mut selection.new_preset_name)
+                .hint_text("name")
+                .desired_width(120.0),
+        );
+        let name = selection.new_preset_name.trim().to_string();
+        if ui
+            .add_enabled(!name.is_empty(), egui::Button::new("Save Selection as Preset"))
+            .on_hover_text("Stores the selected and Partial files; an existing preset of that name is replaced")
+            .clicked()
+        {
+            match Preset::from_selection(
+                &name,
+                &selection.available_files,
+                &selection.selected_files,
+                &selection.partial_files,
+            ) {
+                Ok(mut preset) => {
+                    if let Some(existing) = selection.presets.iter().find(|p| p.name == name) {
+                        preset.instructions = existing.instructions.clone();
+                        preset.replacing_rules = existing.replacing_rules.clone();
+                    }
+                    save = Some(preset);
+                }
+                Err(e) => selection.preset_error = Some(e),
+            }
+        }
+    });
+
+    if let Some(name) = selection.preset.clone() {
+        egui::CollapsingHeader::new("Preset instructions and rules")
+            .id_salt("preset_overrides")
+            .show(ui, |ui| {
+                let Some(preset) = selection.presets.iter_mut().find(|p| p.name == name) else {
+                    return;
+                };
+                let mut changed = false;
+                for (label, value, default) in [
+                    ("Own instructions", &mut preset.instructions, &config.instructions),
+                    ("Own replacing rules", &mut preset.replacing_rules, &config.replacing_rules),
+                ] {
+                    let mut is_set = value.is_some();
+                    if ui.checkbox(&mut is_set, label).changed() {
+                        *value = is_set.then(|| default.clone());
+                        changed = true;
+                    }
+                    if let Some(text) = value {
+                        changed |= ui.add(egui::TextEdit::multiline(text).desired_rows(4)).lost_focus();
+                    }
+                }
+                if changed {
+                    save = Some(preset.clone());
+                }
+            });
+    }
+
+    if let Some(preset) = chosen {
+        let (mut selected, partials) = preset.selection(&selection.available_files);
+        for file in selection.skipped.keys() {
+            selected.insert(file.clone(), false);
+        }
+        selection.selected_files = selected;
+        selection.partial_files = partials;
+        selection.preset = Some(preset.name);
+        selection.unpublished = true;
+    }
+    if let Some(preset) = save {
+        let name = preset.name.clone();
+        match save_preset(root, preset) {
+            Ok(()) => {
+                selection.preset = Some(name);
+                selection.unpublished = true;
+                selection.new_preset_name.clear();
+                selection.reload_presets(root);
+            }
+            Err(e) => selection.preset_error = Some(e),
+        }
+    }
+    if delete {
+        if let Some(name) = selection.preset.take() {
+            selection.unpublished = true;
+            if let Err(e) = delete_preset(root, &name) {
+                selection.preset_error = Some(e);
+            }
+            selection.reload_presets(root);
+        }
+    }
+    if let Some(error) = &selection.preset_error {
+        ui.colored_label(egui::Color32::RED, format!("Presets: {}", error));
+    }
+}
+
+/// Draws a Full / Signature / Excluded choice per symbol, with the members of a type
+/// indented below it. Returns whether a mode changed.
+fn symbol_rows(ui: &mut egui::Ui, symbols: &[Symbol], modes: &mut SymbolModes, inherited: SymbolMode) -> bool {
+    let mut changed = false;
+    for symbol in symbols {
+        let mut mode = modes.get(&symbol.path).copied().unwrap_or(inherited);
+        ui.horizontal(|ui| {
+            for (value, label) in [
+                (SymbolMode::Full, "Full"),
+                (SymbolMode::Signature, "Signature"),
+                (SymbolMode::Excluded, "Excluded"),
+            ] {
+                if ui.selectable_value(&mut mode, value, label).changed() {
+                    // An override equal to what the symbol inherits is dropped again.
+                    if mode == inherited {
+                        modes.remove(&symbol.path);
+                    } else {
+                        modes.insert(symbol.path.clone(), mode);
+                    }
+                    changed = true;
+                }
+            }
+            let icon = match symbol.kind {
+                SymbolKind::Function => "ƒ",
+                SymbolKind::Type => "◆",
+            };
+            let name = egui::RichText::new(format!("{icon} {}", symbol.name));
+            ui.label(if mode == SymbolMode::Excluded { name.strikethrough().weak() } else { name });
+        });
+        if symbol.kind == SymbolKind::Type && !symbol.children.is_empty() && mode != SymbolMode::Excluded {
+            ui.indent(&symbol.path, |ui| {
+                changed |= symbol_rows(ui, &symbol.children, modes, mode);
+            });
+        }
+    }
+    changed
+}
+
+/// Short token count for labels, e.g. `850` or `12.3k`.
+fn format_tokens(n: usize) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", n as f64 / 1_000.0),
+        _ => format!("{:.1}M", n as f64 / 1_000_000.0),
+    }
+}
+
+/// Patch list actions that need the whole list, applied once the list has been drawn.
+enum PatchListAction {
+    /// A per-block button; on a block of a response group it acts on the whole group.
+    Act(usize, PatchAction),
+    ApplyGroup(String),
+    UndoGroup(String),
+}
+
+/// A per-block button, explaining that it acts on the block's whole response group.
+fn group_button(ui: &mut egui::Ui, label: &str, patch: &PatchEntry) -> egui::Response {
+    let button = ui.button(label);
+    if patch.group_id.is_some() {
+        button.on_hover_text("Acts on every block of this AI response")
+    } else {
+        button
+    }
+}
+
+/// One patch of the list. `can_dismiss` is false while the patch, or a block of its
+/// response group, is applied.
+fn patch_entry_ui(
+    ui: &mut egui::Ui,
+    i: usize,
+    patch: &mut PatchEntry,
+    can_dismiss: bool,
+    expanded: &mut Option<String>,
+    action: &mut Option<PatchListAction>,
+) {
+    let apply = PatchAction::Apply { line: None };
+    ui.push_id(i, |ui| {
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                let is_expanded = expanded.as_ref() == Some(&patch.id);
+                let icon = if is_expanded { "▼" } else { "▶" };
+                if ui.button(icon).clicked() {
+                    if is_expanded {
+                        *expanded = None;
+                    } else {
+                        *expanded = Some(patch.id.clone());
+                    }
+                }
+
+                match &patch.status {
+                    PatchStatus::Queued => {
+                        if group_button(ui, "Apply Now", patch).clicked() { *action = Some(PatchListAction::Act(i, apply)); }
+                    },
+                    PatchStatus::Success => {
+                        if group_button(ui, "Undo", patch).clicked() {
+                            *action = Some(PatchListAction::Act(i, PatchAction::Undo { force: false }));
+                        }
+                    },
+                    PatchStatus::Pending => {
+                        if group_button(ui, "Apply", patch).clicked() { *action = Some(PatchListAction::Act(i, apply)); }
+                    },
+                    PatchStatus::Failed(_) => {
+                        if group_button(ui, "Retry", patch).clicked() { *action = Some(PatchListAction::Act(i, apply)); }
+                    },
+                    PatchStatus::Ambiguous(lines) => {
+                        let mut chosen = None;
+                        ui.menu_button("Pick Match", |ui| {
+                            for line in lines {
+                                if ui.button(format!("Replace at line {}", line)).clicked() {
+                                    chosen = Some(*line);
+                                    ui.close();
+                                }
+                            }
+                        });
+                        if chosen.is_some() {
+                            *action = Some(PatchListAction::Act(i, PatchAction::Apply { line: chosen }));
+                        }
+                    }
+                }
+
+                let (hover, disabled_hover) = if patch.group_id.is_some() {
+                    ("Dismiss every block of this AI response", "Undo the applied blocks of this AI response before dismissing")
+                } else {
+                    ("Dismiss", "Undo the patch before dismissing it")
+                };
+                if ui
+                    .add_enabled(can_dismiss, egui::Button::new("✖"))
+                    .on_hover_text(hover)
+                    .on_disabled_hover_text(disabled_hover)
+                    .clicked()
+                {
+                    *action = Some(PatchListAction::Act(i, PatchAction::Dismiss));
+                }
+
+                ui.separator();
+
+                match &patch.status {
+                    PatchStatus::Queued => ui.colored_label(egui::Color32::GRAY, "QUEUED"),
+                    PatchStatus::Pending => ui.colored_label(egui::Color32::YELLOW, "PENDING"),
+                    PatchStatus::Success => ui.colored_label(egui::Color32::GREEN, "SUCCESS"),
+                    PatchStatus::Failed(_) => ui.colored_label(egui::Color32::RED, "FAILED"),
+                    PatchStatus::Ambiguous(_) => ui.colored_label(egui::Color32::ORANGE, "AMBIGUOUS"),
+                };
+
+                // A pending patch with match info is a fuzzy match auto-apply left for review.
+                if let (PatchStatus::Success | PatchStatus::Pending, Some(info)) = (&patch.status, &patch.match_info) {
+                    let text = format!("{} {:.0}%", info.tier.label(), info.score * 100.0);
+                    if info.tier == MatchTier::Exact {
+                        ui.label(egui::RichText::new(text).weak());
+                    } else {
+                        let color = if info.tier == MatchTier::Fuzzy { egui::Color32::ORANGE } else { egui::Color32::LIGHT_BLUE };
+                        let hint = if patch.status == PatchStatus::Success { "Review the applied change." } else { "Review the change before applying it." };
+                        ui.colored_label(color, text).on_hover_text(format!("SEARCH text did not match exactly. {}", hint));
+                    }
+                }
+
+                if let Some(warning) = &patch.warning {
+                    ui.colored_label(egui::Color32::YELLOW, "⚠").on_hover_text(warning);
+                }
+
+                match patch.data.operation {
+                    PatchOperation::Replace => {}
+                    PatchOperation::Create => { ui.colored_label(egui::Color32::LIGHT_GREEN, "CREATE"); }
+                    PatchOperation::Delete => { ui.colored_label(egui::Color32::LIGHT_RED, "DELETE"); }
+                    PatchOperation::Rename => { ui.colored_label(egui::Color32::LIGHT_BLUE, "RENAME"); }
+                }
+                let title = match (&patch.data.operation, &patch.data.new_path) {
+                    (PatchOperation::Rename, Some(to)) => format!("{} → {}", patch.data.file_path, to),
+                    _ => patch.data.file_path.clone(),
+                };
+                ui.label(egui::RichText::new(title).strong());
+                if let (PatchOperation::Replace, Some(hint)) = (&patch.data.operation, patch.data.line_hint) {
+                    ui.label(egui::RichText::new(format!("hunk @ line {}", hint)).weak());
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(egui::RichText::new(&patch.timestamp).weak());
+                });
+            });
+
+            if expanded.as_ref() == Some(&patch.id) {
+                ui.separator();
+                if let Some(warning) = &patch.warning {
+                    ui.colored_label(egui::Color32::YELLOW, format!("Warning: {}", warning));
+                }
+                if let (Some(current), Some(backup)) = (&patch.undo_conflict, &patch.backup_content) {
+                    ui.colored_label(egui::Color32::ORANGE, "The file changed after this patch was applied and the patch could not be reversed in place.");
+                    ui.label("Restoring the backup would make these changes (- current file, + backup):");
+                    let diff = similar::TextDiff::from_lines(current, backup)
+                        .unified_diff()
+                        .context_radius(3)
+                        .to_string();
+                    egui::ScrollArea::vertical().id_salt("undo_conflict_diff").max_height(250.0).show(ui, |ui| {
+                        for line in diff.lines() {
+                            let color = if line.starts_with('+') {
+                                egui::Color32::GREEN
+                            } else if line.starts_with('-') {
+                                egui::Color32::RED
+                            } else {
+                                egui::Color32::GRAY
+                            };
+                            ui.label(egui::RichText::new(line).monospace().color(color));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore Backup Anyway").clicked() {
+                            *action = Some(PatchListAction::Act(i, PatchAction::Undo { force: true }));
+                        }
+                        if ui.button("Cancel").clicked() {
+                            patch.undo_conflict = None;
+                        }
+                    });
+                    ui.separator();
+                }
+                if let PatchStatus::Failed(err) = &patch.status {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}", err));
+                    if ui.button("Copy Error Report for AI").clicked() {
+                        let report = if patch.data.operation == PatchOperation::Replace {
+                            format!("The following replace could not be found:\n\n{}\n\nPlease check indentation/tabs.", patch.data.to_block())
+                        } else {
+                            format!("The following file operation failed ({}):\n\n{}\n\nPlease check the file paths.", err, patch.data.to_block())
+                        };
+                        if let Ok(mut cb) = arboard::Clipboard::new() { let _ = cb.set_text(report); }
+                    }
+                }
+                if let PatchStatus::Ambiguous(lines) = &patch.status {
+                    let count = lines.len();
+                    let lines = lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join(", ");
+                    ui.colored_label(egui::Color32::ORANGE, format!("Search text matches {} places (lines {}). Pick one above.", count, lines));
+                    if ui.button("Copy Error Report for AI").clicked() {
+                        let report = format!(
+                            "The following search text is ambiguous, it appears at lines {} of {}:\n\n{}\n\nPlease resend the block with a few more unchanged context lines above and below so it matches exactly once.",
+                            lines, patch.data.file_path, patch.data.to_block()
+                        );
+                        if let Ok(mut cb) = arboard::Clipboard::new() { let _ = cb.set_text(report); }
+                    }
+                }
+
+                match patch.data.operation {
+                    PatchOperation::Replace => {
+                        ui.columns(2, |cols| {
+                            cols[0].label("Search:");
+                            cols[0].add(egui::TextEdit::multiline(&mut patch.data.search_content.as_str()).code_editor().interactive(false));
+                            cols[1].label("Replace:");
+                            cols[1].add(egui::TextEdit::multiline(&mut patch.data.replace_content.as_str()).code_editor().interactive(false));
+                        });
+                    }
+                    PatchOperation::Create => {
+                        ui.label("New file content:");
+                        ui.add(egui::TextEdit::multiline(&mut patch.data.replace_content.as_str()).code_editor().desired_width(f32::INFINITY).interactive(false));
+                    }
+                    PatchOperation::Delete => {
+                        ui.label(format!("Deletes {}", patch.data.file_path));
+                    }
+                    PatchOperation::Rename => {
+                        ui.label(format!("Moves {} to {}", patch.data.file_path, patch.data.new_path.as_deref().unwrap_or_default()));
+                    }
+                }
+            }
+        });
+    });
+    ui.add_space(2.0);
+}
+
+/// Writes the config to disk and hands it to the running server.
+fn persist_config(config: &AppConfig, server_config: &SharedConfigRef) {
+    save_config(config);
+    *server_config.lock() = config.clone();
+}
+
+/// The userscript with this install's port and API token filled in.
+fn userscript(config: &AppConfig) -> String {
+    TAMPERMONKEY_SCRIPT
+        .replace("127.0.0.1:3030", &format!("127.0.0.1:{}", config.port))
+        .replace("const API_TOKEN = \"\";", &format!("const API_TOKEN = \"{}\";", config.api_token))
+}
+
+#[rustfmt::skip]
+const TAMPERMONKEY_SCRIPT: &str = r#"
+// ==UserScript==
+// @name         BetterPaste Connector
+// @namespace    http://tampermonkey.net/
+// @version      1.12
+// @description  Scans AI chat for BetterPaste code blocks
+// @match        https://chatgpt.com/*
+// @match        https://gemini.google.com/*
+// @match        https://claude.ai/*
+// @match        https://chat.deepseek.com/*
+// @match        https://aistudio.google.com/*
+// @connect      127.0.0.1
+// @grant        GM_xmlhttpRequest
+// @run-at       document-idle
+// ==/UserScript==
+
+(function() {
+    'use strict';
+
+    const SERVER_URL = "http://127.0.0.1:3030/api/batch";
+    const API_TOKEN = ""; // From BetterPaste's Help tab
+    const CONTEXT_URL = SERVER_URL.replace("/api/batch", "/api/context");
+    const SCAN_INTERVAL_MS = 1000;
+
+    let isScanning = false; // Start Paused
+    let inFlight = false; // One batch at a time, so a slow reply isn't sent twice
+    let lastBodyText = null; // Page text from the previous tick, to wait for streaming to finish
+    let cornerIndex = 0; // 0=BR, 1=BL, 2=TL, 3=TR
+
+    const uiContainer = document.createElement('div');
+    uiContainer.style.cssText = 'position:fixed; z-index:9999; display:flex; align-items:center; gap:8px; padding:6px 10px; background:#222; border:1px solid #444; color:#fff; border-radius:6px; font-family:sans-serif; font-size:12px; box-shadow:0 4px 6px rgba(0,0,0,0.3); transition:all 0.3s ease;';
+
+    const statusText = document.createElement('span');
+    statusText.innerText = "BP: Paused";
+    statusText.style.fontWeight = "bold";
+    statusText.style.minWidth = "70px";
+
+    const toggleBtn = document.createElement('button');
+    toggleBtn.innerText = "▶";
+    toggleBtn.style.cssText = 'background:#444; color:white; border:none; padding:4px 8px; border-radius:4px; cursor:pointer; font-size:12px;';
+
+    const moveBtn = document.createElement('button');
+    moveBtn.innerText = "✥";
+    moveBtn.style.cssText = 'background:#444; color:white; border:none; padding:4px 8px; border-radius:4px; cursor:pointer; font-size:12px;';
+
+    const contextBtn = document.createElement('button');
+    contextBtn.innerText = "⇩";
+    contextBtn.title = "Insert the current BetterPaste context into the chat input";
+    contextBtn.style.cssText = moveBtn.style.cssText;
+
+    uiContainer.appendChild(statusText);
+    uiContainer.appendChild(toggleBtn);
+    uiContainer.appendChild(contextBtn);
+    uiContainer.appendChild(moveBtn);
+    document.body.appendChild(uiContainer);
+
+    const applyPosition = () => {
+        uiContainer.style.top = uiContainer.style.bottom = uiContainer.style.left = uiContainer.style.right = 'auto';
+        const margin = '15px';
+        if (cornerIndex === 0) { uiContainer.style.bottom = margin; uiContainer.style.right = margin; }
+        else if (cornerIndex === 1) { uiContainer.style.bottom = margin; uiContainer.style.left = margin; }
+        else if (cornerIndex === 2) { uiContainer.style.top = margin; uiContainer.style.left = margin; }
+        else if (cornerIndex === 3) { uiContainer.style.top = margin; uiContainer.style.right = margin; }
+    };
+    applyPosition();
+
+    toggleBtn.onclick = () => {
+        isScanning = !isScanning;
+        if (isScanning) {
+            toggleBtn.innerText = "⏸";
+            statusText.innerText = "BP: Idle";
+            statusText.style.color = " #fff";
+            scanForBlocks();
+        } else {
+            toggleBtn.innerText = "▶";
+            statusText.innerText = "BP: Paused";
+            statusText.style.color = " #aaa";
+        }
+    };
+
+    moveBtn.onclick = () => { cornerIndex = (cornerIndex + 1) % 4; applyPosition(); };
+
+    // The chat input loses focus to the button, so remember the last one typed into.
+    let lastInput = null;
+    document.addEventListener('focusin', (e) => {
+        if (e.target.tagName === 'TEXTAREA' || e.target.isContentEditable) lastInput = e.target;
+    });
+
+    contextBtn.onclick = () => {
+        const input = lastInput || document.querySelector('textarea, [contenteditable="true"]');
+        if (!input) return;
+        contextBtn.innerText = "…";
+        GM_xmlhttpRequest({
+            method: "GET",
+            url: CONTEXT_URL,
+            headers: { "X-BetterPaste-Token": API_TOKEN },
+            onload: function(res) {
+                contextBtn.innerText = "⇩";
+                if (res.status < 200 || res.status >= 300) {
+                    contextBtn.title = res.status === 401 || res.status === 403 ? "Err: Token" : "Err: Backend";
+                    return;
+                }
+                const context = JSON.parse(res.responseText);
+                contextBtn.title = `Inserted ${context.files} files, ~${context.tokens} tokens`;
+                input.focus();
+                // insertText goes through the page's own input handling, unlike setting .value
+                document.execCommand('insertText', false, "```xml\n" + context.xml + "\n```");
+            },
+            onerror: function() {
+                contextBtn.innerText = "⇩";
+                contextBtn.title = "Err: Connect";
+            }
+        });
+    };
+
+    const BLOCK_REGEX = /\[<\(x\{START\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{SEARCH\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{REPLACEWITH\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]/g;
+    const CREATE_REGEX = /\[<\(x\{CREATE\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{CONTENT\}x\)>\][^\S\n]*\n?([\s\S]*?)[^\S\n]*\[<\(x\{END\}x\)>\]/g;
+    const DELETE_REGEX = /\[<\(x\{DELETE\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]/g;
+    const RENAME_REGEX = /\[<\(x\{RENAME\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{TO\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]/g;
+    // The rules and example of a pasted context describe the markers; they are not patches.
+    const RULES_REGEX = /<ReplacingRules>[\s\S]*?<\/ReplacingRules>/g;
+
+    // What the user wrote is never a reply: the chat input (where the context button pastes
+    // the rules and files) and the user's own messages are left out of the scan.
+    const USER_AUTHORED = 'textarea, input, [contenteditable]:not([contenteditable="false"]), [data-message-author-role="user"], [data-testid="user-message"]';
+
+    // The page's text without user-authored parts. Only the elements that contain one are
+    // split up; everything else is read whole so its line breaks stay as the page shows them.
+    function replyText(node) {
+        if (node.nodeType === Node.TEXT_NODE) return node.textContent;
+        if (node.nodeType !== Node.ELEMENT_NODE || node.matches(USER_AUTHORED)) return '';
+        if (node.checkVisibility && !node.checkVisibility()) return '';
+        if (!node.querySelector(USER_AUTHORED)) return node.innerText;
+        return Array.from(node.childNodes, replyText).join('\n');
+    }
+
+    function updateStatus(msg, color = null) {
+        if (!isScanning) return;
+        statusText.innerText = msg;
+        if (color) statusText.style.color = color;
+    }
+
+    // Collects every block on the page as { index, fullMatch, payload }, in page order.
+    function findBlocks(bodyText) {
+        const blocks = [];
+        bodyText = bodyText.replace(RULES_REGEX, (rules) => ' '.repeat(rules.length)); // Keeps indices
+        const collect = (regex, toPayload) => {
+            regex.lastIndex = 0;
+            let match;
+            while ((match = regex.exec(bodyText)) !== null) {
+                const payload = toPayload(match);
+                if (payload) blocks.push({ index: match.index, fullMatch: match[0], payload });
+            }
+        };
+
+        collect(BLOCK_REGEX, (match) => {
+            const filePath = match[1].trim();
+            const searchBlock = match[2];
+            if (searchBlock.length > 60 && !searchBlock.includes('\n')) {
+                console.warn(`[BetterPaste] Skipping suspicious flattened block for ${filePath}`);
+                return null;
+            }
+            return { file_path: filePath, search_content: searchBlock, replace_content: match[3] };
+        });
+        collect(CREATE_REGEX, (match) => ({ operation: "create", file_path: match[1].trim(), replace_content: match[2] }));
+        collect(DELETE_REGEX, (match) => ({ operation: "delete", file_path: match[1].trim() }));
+        collect(RENAME_REGEX, (match) => ({ operation: "rename", file_path: match[1].trim(), new_path: match[2].trim() }));
+
+        return blocks.sort((a, b) => a.index - b.index);
+    }
+
+    // Unified diffs (--- a/ +++ b/ @@) are sent whole; BetterPaste splits them into hunks.
+    const UNIFIED_DIFF_REGEX = /^--- .*\n\+\+\+ .*\n@@ /m;
+
+    function findUnifiedDiffs() {
+        return Array.from(document.querySelectorAll('pre'))
+            .filter((pre) => !pre.closest(USER_AUTHORED))
+            .map((pre) => pre.innerText)
+            .filter((text) => UNIFIED_DIFF_REGEX.test(text) && !text.includes('[<(x{'))
+            .map((text) => ({ fullMatch: text, payload: { unified_diff: text } }));
+    }
+
+    // Everything new from one scan is sent as a single batch, so BetterPaste can apply the
+    // whole AI reply (or none of it) as one response group.
+    function scanForBlocks() {
+        if (!isScanning || inFlight) return;
+        const bodyText = replyText(document.body);
+        if (bodyText !== lastBodyText) {
+            lastBodyText = bodyText; // Still streaming, wait until the reply stops changing
+            return;
+        }
+
+        const patches = [];
+        const diffs = [];
+        const hashes = [];
+        for (const { fullMatch, payload } of findBlocks(bodyText).concat(findUnifiedDiffs())) {
+            const normalizedContent = fullMatch.replace(/\s/g, '');
+            const blockHash = cyrb53(normalizedContent);
+
+            if (sessionStorage.getItem(`bp_sent_${blockHash}`) || hashes.includes(blockHash)) continue;
+            hashes.push(blockHash);
+            if (payload.unified_diff) diffs.push(payload.unified_diff);
+            else patches.push(payload);
+        }
+        if (hashes.length === 0) return;
+
+        updateStatus(`Sending ${hashes.length}...`, ' #e67e22');
+        inFlight = true;
+
+        GM_xmlhttpRequest({
+            method: "POST",
+            url: SERVER_URL,
+            headers: { "Content-Type": "application/json", "X-BetterPaste-Token": API_TOKEN },
+            data: JSON.stringify({ patches, text: diffs.length ? diffs.join("\n") : null }),
+            onload: function(res) {
+                inFlight = false;
+                if (res.status >= 200 && res.status < 300) {
+                    hashes.forEach((hash) => sessionStorage.setItem(`bp_sent_${hash}`, "true"));
+                    // One receipt per patch: { id, file_path, status, reason }
+                    let receipts = [];
+                    try { receipts = JSON.parse(res.responseText).patches || []; } catch (e) { /* older BetterPaste */ }
+                    const failed = receipts.filter((r) => r.status === "failed");
+                    failed.forEach((r) => console.warn(`[BetterPaste] ${r.file_path}: ${r.reason}`));
+                    if (failed.length) {
+                        updateStatus(`Failed ${failed.length}/${receipts.length}`, ' #c0392b');
+                    } else if (receipts.length && receipts.every((r) => r.status === "dismissed")) {
+                        updateStatus("Dismissed", ' #aaa');
+                    } else {
+                        updateStatus(receipts.some((r) => r.status === "success") ? "Applied" : "Synced", '#27ae60');
+                    }
+                    setTimeout(() => updateStatus("BP: Idle", ' #fff'), failed.length ? 5000 : 2000);
+                } else if (res.status === 401 || res.status === 403) {
+                    updateStatus("Err: Token", ' #c0392b');
+                } else {
+                    updateStatus("Err: Backend", ' #c0392b');
+                }
+            },
+            onerror: function() {
+                inFlight = false;
+                updateStatus("Err: Connect", ' #c0392b');
+            }
+        });
+    }
+
+    const cyrb53 = function(str, seed = 0) {
+        let h1 = 0xdeadbeef ^ seed, h2 = 0x41c6ce57 ^ seed;
+        for (let i = 0, ch; i < str.length; i++) {
+            ch = str.charCodeAt(i);
+            h1 = Math.imul(h1 ^ ch, 2654435761);
+            h2 = Math.imul(h2 ^ ch, 1597334677);
+        }
+        h1 = Math.imul(h1 ^ (h1 >>> 16), 2246822507) ^ Math.imul(h2 ^ (h2 >>> 13), 3266489909);
+        h2 = Math.imul(h2 ^ (h2 >>> 16), 2246822507) ^ Math.imul(h1 ^ (h1 >>> 13), 3266489909);
+        return 4294967296 * (2097151 & h2) + (h1 >>> 0);
+    };
+
+    setInterval(scanForBlocks, SCAN_INTERVAL_MS);
+})();
+"#;
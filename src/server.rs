@@ -0,0 +1,653 @@
+//! Local HTTP endpoints the userscript posts patches to, and the patch list API for other
+//! local tools.
+
+use crate::{SharedConfigRef, SharedStateRef};
+use axum::{
+    Router,
+    extract::{
+        FromRef, Json, Path, Query, Request, State,
+        rejection::{JsonRejection, QueryRejection},
+    },
+    http::{HeaderName, HeaderValue, Method, StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use betterpaste_core::{
+    API_TOKEN_HEADER, IncomingPatch, PatchAction, PatchEntry, PatchOperation, PatchReceipt,
+    QueueEvent, Workspace, act_on_patch, build_context, load_presets, parse_patch_blocks, parse_unified_diff,
+    receive_group, receive_patch,
+};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Router state: the app state the handlers act on, the live config and the event channel
+/// `/api/events` subscribes to.
+#[derive(Clone)]
+struct ServerState {
+    app: SharedStateRef,
+    config: SharedConfigRef,
+    events: broadcast::Sender<QueueEvent>,
+}
+
+impl FromRef<ServerState> for SharedConfigRef {
+    fn from_ref(state: &ServerState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<ServerState> for SharedStateRef {
+    fn from_ref(state: &ServerState) -> Self {
+        state.app.clone()
+    }
+}
+
+impl FromRef<ServerState> for broadcast::Sender<QueueEvent> {
+    fn from_ref(state: &ServerState) -> Self {
+        state.events.clone()
+    }
+}
+
+/// Body of `POST /api/diff`: a single patch, or a unified diff that may hold many hunks.
+/// `project` names the target workspace; without it the patch is routed by its file paths.
+#[derive(Deserialize)]
+struct DiffRequest {
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(flatten)]
+    body: DiffBody,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DiffBody {
+    Patch(IncomingPatch),
+    UnifiedDiff { unified_diff: String },
+}
+
+/// Body of `POST /api/batch`: explicit patches and/or raw AI response text to parse.
+#[derive(Deserialize)]
+struct BatchRequest {
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    patches: Vec<IncomingPatch>,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+/// Response to `POST /api/diff` and `POST /api/batch`: the workspace the patches went to and
+/// one receipt per patch, in the order they were sent (for `/api/batch`, explicit patches
+/// first, then those parsed from `text`).
+#[derive(Serialize)]
+struct ReceiveResponse {
+    project: String,
+    patches: Vec<PatchReceipt>,
+}
+
+/// Body of every 4xx response.
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn api_error(status: StatusCode, error: impl Into<String>) -> ApiError {
+    (status, Json(ErrorResponse { error: error.into() }))
+}
+
+/// Unparseable JSON is a 400, well-formed JSON of the wrong shape a 422, and a missing
+/// `Content-Type: application/json` a 415.
+fn json_error(rejection: JsonRejection) -> ApiError {
+    api_error(rejection.status(), rejection.body_text())
+}
+
+/// Runs `work` on a blocking thread. Receiving, applying and undoing patches read and write
+/// files while holding the app state's lock, which must not stall the async runtime.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+}
+
+/// Prints what became of each received patch, one line per patch.
+fn log_receipts(project: &str, receipts: &[PatchReceipt]) {
+    for receipt in receipts {
+        println!("[{}] {}", project, receipt);
+    }
+}
+
+async fn batch_handler(
+    State(state): State<SharedStateRef>,
+    payload: Result<Json<BatchRequest>, JsonRejection>,
+) -> Result<Json<ReceiveResponse>, ApiError> {
+    let Json(payload) = payload.map_err(json_error)?;
+    let mut patches = payload.patches;
+    if let Some(text) = &payload.text {
+        patches.extend(parse_patch_blocks(text));
+    }
+    if patches.is_empty() {
+        return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "No patches in the request"));
+    }
+    blocking(move || {
+        let mut app_state = state.lock();
+        let index = app_state
+            .route(payload.project.as_deref(), &patches)
+            .map_err(|e| api_error(StatusCode::NOT_FOUND, e))?;
+        let workspace = &mut app_state.workspaces[index];
+        let receipts = receive_group(workspace, patches);
+        log_receipts(&workspace.id, &receipts);
+        Ok(Json(ReceiveResponse { project: workspace.id.clone(), patches: receipts }))
+    })
+    .await
+}
+
+async fn diff_handler(
+    State(state): State<SharedStateRef>,
+    payload: Result<Json<DiffRequest>, JsonRejection>,
+) -> Result<Json<ReceiveResponse>, ApiError> {
+    let Json(payload) = payload.map_err(json_error)?;
+    let patches = match payload.body {
+        DiffBody::Patch(patch) => vec![patch],
+        DiffBody::UnifiedDiff { unified_diff } => {
+            let patches: Vec<IncomingPatch> =
+                parse_unified_diff(&unified_diff).into_iter().map(|(_, p)| p).collect();
+            if patches.is_empty() {
+                return Err(api_error(StatusCode::UNPROCESSABLE_ENTITY, "No hunks in the unified diff"));
+            }
+            patches
+        }
+    };
+
+    blocking(move || {
+        let mut app_state = state.lock();
+        let index = app_state
+            .route(payload.project.as_deref(), &patches)
+            .map_err(|e| api_error(StatusCode::NOT_FOUND, e))?;
+        let workspace = &mut app_state.workspaces[index];
+        let receipts: Vec<PatchReceipt> =
+            patches.into_iter().map(|patch| receive_patch(workspace, patch)).collect();
+        log_receipts(&workspace.id, &receipts);
+
+        Ok(Json(ReceiveResponse { project: workspace.id.clone(), patches: receipts }))
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Rejects requests from origins outside `AppConfig::allowed_origins` (403) and requests
+/// without the right API token (401), logging each rejection. Both are read from the live
+/// config on every request.
+async fn authorize(
+    State(config): State<SharedConfigRef>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let headers = request.headers();
+    let origin = headers.get(header::ORIGIN).map(|o| o.to_str().unwrap_or("<invalid>"));
+    let rejection = {
+        let config = config.lock();
+        if !config.origin_allowed(origin) {
+            Some((StatusCode::FORBIDDEN, "origin not allowed"))
+        } else {
+            // EventSource cannot send headers, so only the event stream takes `?token=`;
+            // anywhere else a token in the URL would end up in logs and history.
+            let query = (request.uri().path() == "/api/events")
+                .then(|| Query::<TokenQuery>::try_from_uri(request.uri()).ok().and_then(|q| q.0.token))
+                .flatten();
+            match headers.get(API_TOKEN_HEADER).and_then(|t| t.to_str().ok()).or(query.as_deref()) {
+                None => Some((StatusCode::UNAUTHORIZED, "missing token")),
+                Some(token) if !config.token_matches(token) => Some((StatusCode::UNAUTHORIZED, "wrong token")),
+                Some(_) => None,
+            }
+        }
+    };
+    if let Some((status, reason)) = rejection {
+        eprintln!(
+            "Rejected {} {} from origin {}: {}",
+            request.method(),
+            request.uri().path(),
+            origin.unwrap_or("<none>"),
+            reason
+        );
+        return Err(api_error(status, reason));
+    }
+    Ok(next.run(request).await)
+}
+
+/// Query of `GET /api/patches`: the workspace to list, by default the one shown in the GUI.
+#[derive(Deserialize)]
+struct ListQuery {
+    project: Option<String>,
+}
+
+/// Query of the `POST /api/patches/{id}/...` actions.
+#[derive(Deserialize)]
+struct ActionQuery {
+    /// For `apply`: start line of the match to replace when the SEARCH block is ambiguous.
+    line: Option<usize>,
+    /// For `undo`: overwrite edits made since the patch was applied.
+    #[serde(default)]
+    force: bool,
+}
+
+/// A patch as listed by the read API. The SEARCH/REPLACE text is only included for a
+/// single patch.
+#[derive(Serialize)]
+struct PatchView<'a> {
+    #[serde(flatten)]
+    receipt: PatchReceipt,
+    project: &'a str,
+    timestamp: &'a str,
+    operation: PatchOperation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_path: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_id: Option<&'a str>,
+    /// An undo found edits made since the patch was applied and waits for a forced undo.
+    undo_conflict: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    search_content: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replace_content: Option<&'a str>,
+}
+
+impl<'a> PatchView<'a> {
+    fn new(workspace: &'a Workspace, patch: &'a PatchEntry, with_content: bool) -> Self {
+        Self {
+            receipt: PatchReceipt::of(patch),
+            project: &workspace.id,
+            timestamp: &patch.timestamp,
+            operation: patch.data.operation,
+            new_path: patch.data.new_path.as_deref(),
+            group_id: patch.group_id.as_deref(),
+            undo_conflict: patch.undo_conflict.is_some(),
+            search_content: with_content.then_some(patch.data.search_content.as_str()),
+            replace_content: with_content.then_some(patch.data.replace_content.as_str()),
+        }
+    }
+}
+
+/// Response to `GET /api/patches`: one workspace's queue switches and patch list.
+#[derive(Serialize)]
+struct PatchList<'a> {
+    project: &'a str,
+    paused: bool,
+    auto_apply: bool,
+    auto_dismiss: bool,
+    patches: Vec<PatchView<'a>>,
+}
+
+async fn list_patches(
+    State(state): State<SharedStateRef>,
+    query: Result<Query<ListQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query.map_err(|e| api_error(e.status(), e.body_text()))?;
+    let app_state = state.lock();
+    let workspace = match &query.project {
+        Some(project) => app_state
+            .workspaces
+            .iter()
+            .find(|w| &w.id == project)
+            .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("No open project '{}'", project)))?,
+        None => app_state.active(),
+    };
+    let list = PatchList {
+        project: &workspace.id,
+        paused: workspace.is_paused,
+        auto_apply: workspace.auto_apply,
+        auto_dismiss: workspace.auto_dismiss,
+        patches: workspace.patches.iter().map(|p| PatchView::new(workspace, p, false)).collect(),
+    };
+    Ok(Json(list).into_response())
+}
+
+async fn get_patch(State(state): State<SharedStateRef>, Path(id): Path<String>) -> Result<Response, ApiError> {
+    let app_state = state.lock();
+    let (w, i) = app_state
+        .find_patch(&id)
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("No patch '{}'", id)))?;
+    let workspace = &app_state.workspaces[w];
+    Ok(Json(PatchView::new(workspace, &workspace.patches[i], true)).into_response())
+}
+
+/// `POST /api/patches/{id}/{apply|undo|dismiss}`: the Patcher tab's buttons. Answers with
+/// the patch's receipt, or 409 when the action does not fit the patch's status.
+async fn patch_action(
+    State(state): State<SharedStateRef>,
+    Path((id, action)): Path<(String, String)>,
+    query: Result<Query<ActionQuery>, QueryRejection>,
+) -> Result<Json<PatchReceipt>, ApiError> {
+    let Query(query) = query.map_err(|e| api_error(e.status(), e.body_text()))?;
+    let action = match action.as_str() {
+        "apply" => PatchAction::Apply { line: query.line },
+        "undo" => PatchAction::Undo { force: query.force },
+        "dismiss" => PatchAction::Dismiss,
+        other => return Err(api_error(StatusCode::NOT_FOUND, format!("Unknown action '{}'", other))),
+    };
+    blocking(move || {
+        let mut app_state = state.lock();
+        let (w, i) = app_state
+            .find_patch(&id)
+            .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("No patch '{}'", id)))?;
+        act_on_patch(&mut app_state.workspaces[w], i, action)
+            .map(Json)
+            .map_err(|e| api_error(StatusCode::CONFLICT, e))
+    })
+    .await
+}
+
+/// Query of `GET /api/context`, or body of `POST /api/context`.
+#[derive(Deserialize, Default)]
+struct ContextRequest {
+    #[serde(default)]
+    project: Option<String>,
+    /// Preset to build from instead of the Generator's current selection.
+    #[serde(default)]
+    preset: Option<String>,
+    /// Outside the Generator's selection: keep binary, oversized and generated files.
+    #[serde(default)]
+    include_skipped: bool,
+}
+
+#[derive(Serialize)]
+struct ContextResponse {
+    project: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preset: Option<String>,
+    /// Selected files.
+    files: usize,
+    /// Estimated size of `xml`.
+    tokens: usize,
+    model: String,
+    /// Context window of `model`, if one is configured.
+    budget: Option<usize>,
+    xml: String,
+}
+
+/// Builds the context the Generator would: from the current selection, the named preset,
+/// or, when the GUI has not shown the project, every file that is not skipped. Uses the
+/// saved configuration, so edits made in the Config tab count once saved. Runs on a
+/// blocking thread, since it reads the whole project.
+async fn context_response(
+    state: SharedStateRef,
+    config: SharedConfigRef,
+    request: ContextRequest,
+) -> Result<Json<ContextResponse>, ApiError> {
+    blocking(move || build_response(&state, &config, request)).await.map(Json)
+}
+
+fn build_response(
+    state: &SharedStateRef,
+    config: &SharedConfigRef,
+    request: ContextRequest,
+) -> Result<ContextResponse, ApiError> {
+    let (project, root, published) = {
+        let app_state = state.lock();
+        let workspace = match &request.project {
+            Some(project) => app_state
+                .workspaces
+                .iter()
+                .find(|w| &w.id == project)
+                .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("No open project '{}'", project)))?,
+            None => app_state.active(),
+        };
+        let published = if request.preset.is_some() { None } else { workspace.selection.clone() };
+        (workspace.id.clone(), workspace.sandbox.root.clone(), published)
+    };
+
+    let preset = match request.preset.as_ref().or(published.as_ref().and_then(|s| s.preset.as_ref())) {
+        Some(name) => Some(
+            load_presets(&root)
+                .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+                .into_iter()
+                .find(|p| &p.name == name)
+                .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("No preset named '{}'", name)))?,
+        ),
+        None => None,
+    };
+    let config = config.lock().clone();
+    let context = build_context(&root, &config, preset.as_ref(), published, request.include_skipped);
+    Ok(ContextResponse {
+        project,
+        preset: preset.map(|p| p.name),
+        files: context.files,
+        tokens: context.tokens,
+        model: config.target_model,
+        budget: context.budget,
+        xml: context.xml,
+    })
+}
+
+async fn context_get(
+    State(state): State<SharedStateRef>,
+    State(config): State<SharedConfigRef>,
+    query: Result<Query<ContextRequest>, QueryRejection>,
+) -> Result<Json<ContextResponse>, ApiError> {
+    let Query(request) = query.map_err(|e| api_error(e.status(), e.body_text()))?;
+    context_response(state, config, request).await
+}
+
+async fn context_post(
+    State(state): State<SharedStateRef>,
+    State(config): State<SharedConfigRef>,
+    payload: Result<Json<ContextRequest>, JsonRejection>,
+) -> Result<Json<ContextResponse>, ApiError> {
+    let Json(request) = payload.map_err(json_error)?;
+    context_response(state, config, request).await
+}
+
+/// `GET /api/events`: a Server-Sent Events stream with one JSON `QueueEvent` per message.
+/// A listener that falls behind gets a `lagged` event with the number of missed events and
+/// should re-read `/api/patches`.
+async fn events_handler(
+    State(state): State<SharedStateRef>,
+    State(events): State<broadcast::Sender<QueueEvent>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = events.subscribe();
+    forward_events(&state, &events);
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => Event::default().json_data(&event).unwrap_or_default(),
+            Err(broadcast::error::RecvError::Lagged(missed)) => Event::default().event("lagged").data(missed.to_string()),
+            Err(broadcast::error::RecvError::Closed) => return None,
+        };
+        Some((Ok(event), receiver))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Subscribes to the app state's queue events, once, on the first `/api/events` listener,
+/// and passes them on to every listener. Until then no events are produced at all.
+fn forward_events(state: &SharedStateRef, events: &broadcast::Sender<QueueEvent>) {
+    let mut app_state = state.lock();
+    if app_state.is_subscribed() {
+        return;
+    }
+    let queue = app_state.subscribe();
+    let events = events.clone();
+    std::thread::spawn(move || {
+        for event in queue {
+            // Fails only while no listener is connected.
+            let _ = events.send(event);
+        }
+    });
+}
+
+/// Serves the API on `127.0.0.1:port`. Token, origin allowlist and context settings are
+/// read from `config` per request; only the port needs a restart.
+pub async fn run_server(state: SharedStateRef, config: SharedConfigRef, port: u16) {
+    let (events, _) = broadcast::channel(256);
+    let app = router(state, config, events);
+    let addr = format!("127.0.0.1:{}", port);
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    println!("Server listening on {}", addr);
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// All endpoints behind the token check, wrapped in CORS that allows the same origins the
+/// token check does.
+fn router(state: SharedStateRef, config: SharedConfigRef, events: broadcast::Sender<QueueEvent>) -> Router {
+    let cors_config = config.clone();
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin.to_str().is_ok_and(|o| cors_config.lock().origin_allowed(Some(o)))
+        }))
+        .allow_methods(vec![Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, HeaderName::from_static("x-betterpaste-token")]);
+    // The CORS layer is outermost so preflight requests, which carry no token, get answered.
+    Router::new()
+        .route("/api/diff", post(diff_handler))
+        .route("/api/batch", post(batch_handler))
+        .route("/api/patches", get(list_patches))
+        .route("/api/patches/{id}", get(get_patch))
+        .route("/api/patches/{id}/{action}", post(patch_action))
+        .route("/api/events", get(events_handler))
+        .route("/api/context", get(context_get).post(context_post))
+        .layer(middleware::from_fn_with_state(config.clone(), authorize))
+        .with_state(ServerState { app: state, config, events })
+        .layer(cors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use betterpaste_core::{AppConfig, SharedAppState};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app(config: &SharedConfigRef) -> Router {
+        let state = Arc::new(Mutex::new(SharedAppState::new(std::env::temp_dir(), &config.lock())));
+        router(state, config.clone(), broadcast::channel(16).0)
+    }
+
+    fn config() -> SharedConfigRef {
+        Arc::new(Mutex::new(AppConfig { api_token: "secret".to_string(), ..AppConfig::default() }))
+    }
+
+    async fn status(config: &SharedConfigRef, uri: &str, token: Option<&str>, origin: Option<&str>) -> StatusCode {
+        let mut request = Request::get(uri);
+        if let Some(token) = token {
+            request = request.header(API_TOKEN_HEADER, token);
+        }
+        if let Some(origin) = origin {
+            request = request.header(header::ORIGIN, origin);
+        }
+        app(config).oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn token_and_origin_are_checked_per_request() {
+        let config = config();
+        assert_eq!(status(&config, "/api/patches", None, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&config, "/api/patches", Some("wrong"), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&config, "/api/patches", Some("secret"), None).await, StatusCode::OK);
+        assert_eq!(
+            status(&config, "/api/patches", Some("secret"), Some("https://evil.example")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&config, "/api/patches", Some("secret"), Some("https://claude.ai")).await,
+            StatusCode::OK
+        );
+
+        // Only the event stream takes the token from the query string.
+        assert_eq!(status(&config, "/api/patches?token=secret", None, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&config, "/api/events?token=secret", None, None).await, StatusCode::OK);
+
+        // A saved config takes effect without a restart.
+        config.lock().api_token = "rotated".to_string();
+        assert_eq!(status(&config, "/api/patches", Some("secret"), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&config, "/api/patches", Some("rotated"), None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn preflight_passes_for_allowed_origins_only() {
+        let config = config();
+        let preflight = |origin: &str| {
+            Request::options("/api/batch")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type,x-betterpaste-token")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app(&config).oneshot(preflight("https://claude.ai")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://claude.ai");
+
+        let response = app(&config).oneshot(preflight("chrome-extension://abc")).await.unwrap();
+        assert!(!response.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        config.lock().allowed_origins.push("chrome-extension://abc".to_string());
+        let response = app(&config).oneshot(preflight("chrome-extension://abc")).await.unwrap();
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "chrome-extension://abc");
+    }
+
+    async fn post(app: &Router, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::post(uri)
+            .header(API_TOKEN_HEADER, "secret")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn patch_actions_answer_with_their_errors() {
+        let root = std::env::temp_dir().join(format!("betterpaste-server-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "one\n").unwrap();
+        let config = config();
+        let state = Arc::new(Mutex::new(SharedAppState::new(root.clone(), &config.lock())));
+        let app = router(state, config.clone(), broadcast::channel(16).0);
+
+        let (status, _) = post(&app, "/api/diff", "{\"file_path\": ").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = post(&app, "/api/diff", r#"{"search_content": "one"}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) =
+            post(&app, "/api/diff", r#"{"file_path": "a.txt", "search_content": "one", "replace_content": "1"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["patches"][0]["status"], "success");
+        let id = body["patches"][0]["id"].as_str().unwrap().to_string();
+
+        // An applied patch cannot be dismissed or applied again, only undone once.
+        let (status, body) = post(&app, &format!("/api/patches/{}/dismiss", id), "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].is_string());
+        let (status, _) = post(&app, &format!("/api/patches/{}/undo", id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(std::fs::read_to_string(root.join("a.txt")).unwrap(), "one\n");
+        let (status, _) = post(&app, &format!("/api/patches/{}/undo", id), "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = post(&app, &format!("/api/patches/{}/revert", id), "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = post(&app, "/api/patches/missing/apply", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = post(&app, &format!("/api/patches/{}/apply?line=x", id), "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
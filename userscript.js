@@ -1,7 +1,7 @@
 // ==UserScript==
 // @name         BetterPaste Connector
 // @namespace    http://tampermonkey.net/
-// @version      1.4
+// @version      1.12
 // @description  Scans AI chat for BetterPaste code blocks
 // @match        https://chatgpt.com/*
 // @match        https://gemini.google.com/*
@@ -16,10 +16,14 @@
 (function() {
     'use strict';
 
-    const SERVER_URL = "http://127.0.0.1:3030/api/diff";
+    const SERVER_URL = "http://127.0.0.1:3030/api/batch";
+    const API_TOKEN = ""; // From BetterPaste's Help tab
+    const CONTEXT_URL = SERVER_URL.replace("/api/batch", "/api/context");
     const SCAN_INTERVAL_MS = 1000;
 
     let isScanning = false; // Start Paused
+    let inFlight = false; // One batch at a time, so a slow reply isn't sent twice
+    let lastBodyText = null; // Page text from the previous tick, to wait for streaming to finish
     let cornerIndex = 0; // 0=BR, 1=BL, 2=TL, 3=TR
 
     const uiContainer = document.createElement('div');
@@ -38,8 +42,14 @@
     moveBtn.innerText = "✥";
     moveBtn.style.cssText = 'background:#444; color:white; border:none; padding:4px 8px; border-radius:4px; cursor:pointer; font-size:12px;';
 
+    const contextBtn = document.createElement('button');
+    contextBtn.innerText = "⇩";
+    contextBtn.title = "Insert the current BetterPaste context into the chat input";
+    contextBtn.style.cssText = moveBtn.style.cssText;
+
     uiContainer.appendChild(statusText);
     uiContainer.appendChild(toggleBtn);
+    uiContainer.appendChild(contextBtn);
     uiContainer.appendChild(moveBtn);
     document.body.appendChild(uiContainer);
 
@@ -69,7 +79,59 @@
 
     moveBtn.onclick = () => { cornerIndex = (cornerIndex + 1) % 4; applyPosition(); };
 
+    // The chat input loses focus to the button, so remember the last one typed into.
+    let lastInput = null;
+    document.addEventListener('focusin', (e) => {
+        if (e.target.tagName === 'TEXTAREA' || e.target.isContentEditable) lastInput = e.target;
+    });
+
+    contextBtn.onclick = () => {
+        const input = lastInput || document.querySelector('textarea, [contenteditable="true"]');
+        if (!input) return;
+        contextBtn.innerText = "…";
+        GM_xmlhttpRequest({
+            method: "GET",
+            url: CONTEXT_URL,
+            headers: { "X-BetterPaste-Token": API_TOKEN },
+            onload: function(res) {
+                contextBtn.innerText = "⇩";
+                if (res.status < 200 || res.status >= 300) {
+                    contextBtn.title = res.status === 401 || res.status === 403 ? "Err: Token" : "Err: Backend";
+                    return;
+                }
+                const context = JSON.parse(res.responseText);
+                contextBtn.title = `Inserted ${context.files} files, ~${context.tokens} tokens`;
+                input.focus();
+                // insertText goes through the page's own input handling, unlike setting .value
+                document.execCommand('insertText', false, "```xml\n" + context.xml + "\n```");
+            },
+            onerror: function() {
+                contextBtn.innerText = "⇩";
+                contextBtn.title = "Err: Connect";
+            }
+        });
+    };
+
     const BLOCK_REGEX = /\[<\(x\{START\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{SEARCH\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{REPLACEWITH\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]/g;
+    const CREATE_REGEX = /\[<\(x\{CREATE\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{CONTENT\}x\)>\][^\S\n]*\n?([\s\S]*?)[^\S\n]*\[<\(x\{END\}x\)>\]/g;
+    const DELETE_REGEX = /\[<\(x\{DELETE\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]/g;
+    const RENAME_REGEX = /\[<\(x\{RENAME\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{TO\}x\)>\]\s*([\s\S]*?)\s*\[<\(x\{END\}x\)>\]/g;
+    // The rules and example of a pasted context describe the markers; they are not patches.
+    const RULES_REGEX = /<ReplacingRules>[\s\S]*?<\/ReplacingRules>/g;
+
+    // What the user wrote is never a reply: the chat input (where the context button pastes
+    // the rules and files) and the user's own messages are left out of the scan.
+    const USER_AUTHORED = 'textarea, input, [contenteditable]:not([contenteditable="false"]), [data-message-author-role="user"], [data-testid="user-message"]';
+
+    // The page's text without user-authored parts. Only the elements that contain one are
+    // split up; everything else is read whole so its line breaks stay as the page shows them.
+    function replyText(node) {
+        if (node.nodeType === Node.TEXT_NODE) return node.textContent;
+        if (node.nodeType !== Node.ELEMENT_NODE || node.matches(USER_AUTHORED)) return '';
+        if (node.checkVisibility && !node.checkVisibility()) return '';
+        if (!node.querySelector(USER_AUTHORED)) return node.innerText;
+        return Array.from(node.childNodes, replyText).join('\n');
+    }
 
     function updateStatus(msg, color = null) {
         if (!isScanning) return;
@@ -77,55 +139,106 @@
         if (color) statusText.style.color = color;
     }
 
-    function scanForBlocks() {
-        if (!isScanning) return;
-        const bodyText = document.body.innerText;
-
-        BLOCK_REGEX.lastIndex = 0;
-        let match;
+    // Collects every block on the page as { index, fullMatch, payload }, in page order.
+    function findBlocks(bodyText) {
+        const blocks = [];
+        bodyText = bodyText.replace(RULES_REGEX, (rules) => ' '.repeat(rules.length)); // Keeps indices
+        const collect = (regex, toPayload) => {
+            regex.lastIndex = 0;
+            let match;
+            while ((match = regex.exec(bodyText)) !== null) {
+                const payload = toPayload(match);
+                if (payload) blocks.push({ index: match.index, fullMatch: match[0], payload });
+            }
+        };
 
-        while ((match = BLOCK_REGEX.exec(bodyText)) !== null) {
-            const fullMatch = match[0];
+        collect(BLOCK_REGEX, (match) => {
             const filePath = match[1].trim();
             const searchBlock = match[2];
-            const replaceBlock = match[3];
+            if (searchBlock.length > 60 && !searchBlock.includes('\n')) {
+                console.warn(`[BetterPaste] Skipping suspicious flattened block for ${filePath}`);
+                return null;
+            }
+            return { file_path: filePath, search_content: searchBlock, replace_content: match[3] };
+        });
+        collect(CREATE_REGEX, (match) => ({ operation: "create", file_path: match[1].trim(), replace_content: match[2] }));
+        collect(DELETE_REGEX, (match) => ({ operation: "delete", file_path: match[1].trim() }));
+        collect(RENAME_REGEX, (match) => ({ operation: "rename", file_path: match[1].trim(), new_path: match[2].trim() }));
+
+        return blocks.sort((a, b) => a.index - b.index);
+    }
+
+    // Unified diffs (--- a/ +++ b/ @@) are sent whole; BetterPaste splits them into hunks.
+    const UNIFIED_DIFF_REGEX = /^--- .*\n\+\+\+ .*\n@@ /m;
+
+    function findUnifiedDiffs() {
+        return Array.from(document.querySelectorAll('pre'))
+            .filter((pre) => !pre.closest(USER_AUTHORED))
+            .map((pre) => pre.innerText)
+            .filter((text) => UNIFIED_DIFF_REGEX.test(text) && !text.includes('[<(x{'))
+            .map((text) => ({ fullMatch: text, payload: { unified_diff: text } }));
+    }
+
+    // Everything new from one scan is sent as a single batch, so BetterPaste can apply the
+    // whole AI reply (or none of it) as one response group.
+    function scanForBlocks() {
+        if (!isScanning || inFlight) return;
+        const bodyText = replyText(document.body);
+        if (bodyText !== lastBodyText) {
+            lastBodyText = bodyText; // Still streaming, wait until the reply stops changing
+            return;
+        }
+
+        const patches = [];
+        const diffs = [];
+        const hashes = [];
+        for (const { fullMatch, payload } of findBlocks(bodyText).concat(findUnifiedDiffs())) {
             const normalizedContent = fullMatch.replace(/\s/g, '');
             const blockHash = cyrb53(normalizedContent);
 
-            if (sessionStorage.getItem(`bp_sent_${blockHash}`)) continue;
+            if (sessionStorage.getItem(`bp_sent_${blockHash}`) || hashes.includes(blockHash)) continue;
+            hashes.push(blockHash);
+            if (payload.unified_diff) diffs.push(payload.unified_diff);
+            else patches.push(payload);
+        }
+        if (hashes.length === 0) return;
 
-            if (searchBlock.length > 60 && !searchBlock.includes('\n')) {
-                console.warn(`[BetterPaste] Skipping suspicious flattened block for ${filePath}`);
-                continue;
-            }
+        updateStatus(`Sending ${hashes.length}...`, ' #e67e22');
+        inFlight = true;
 
-            updateStatus(`Sending...`, ' #e67e22');
-
-            const payload = JSON.stringify({
-                file_path: filePath,
-                search_content: searchBlock,
-                replace_content: replaceBlock
-            });
-
-            GM_xmlhttpRequest({
-                method: "POST",
-                url: SERVER_URL,
-                headers: { "Content-Type": "application/json" },
-                data: payload,
-                onload: function(res) {
-                    if (res.status >= 200 && res.status < 300) {
-                        sessionStorage.setItem(`bp_sent_${blockHash}`, "true");
-                        updateStatus("Synced", '#27ae60');
-                        setTimeout(() => updateStatus("BP: Idle", ' #fff'), 2000);
+        GM_xmlhttpRequest({
+            method: "POST",
+            url: SERVER_URL,
+            headers: { "Content-Type": "application/json", "X-BetterPaste-Token": API_TOKEN },
+            data: JSON.stringify({ patches, text: diffs.length ? diffs.join("\n") : null }),
+            onload: function(res) {
+                inFlight = false;
+                if (res.status >= 200 && res.status < 300) {
+                    hashes.forEach((hash) => sessionStorage.setItem(`bp_sent_${hash}`, "true"));
+                    // One receipt per patch: { id, file_path, status, reason }
+                    let receipts = [];
+                    try { receipts = JSON.parse(res.responseText).patches || []; } catch (e) { /* older BetterPaste */ }
+                    const failed = receipts.filter((r) => r.status === "failed");
+                    failed.forEach((r) => console.warn(`[BetterPaste] ${r.file_path}: ${r.reason}`));
+                    if (failed.length) {
+                        updateStatus(`Failed ${failed.length}/${receipts.length}`, ' #c0392b');
+                    } else if (receipts.length && receipts.every((r) => r.status === "dismissed")) {
+                        updateStatus("Dismissed", ' #aaa');
                     } else {
-                        updateStatus("Err: Backend", ' #c0392b');
+                        updateStatus(receipts.some((r) => r.status === "success") ? "Applied" : "Synced", '#27ae60');
                     }
-                },
-                onerror: function() {
-                    updateStatus("Err: Connect", ' #c0392b');
+                    setTimeout(() => updateStatus("BP: Idle", ' #fff'), failed.length ? 5000 : 2000);
+                } else if (res.status === 401 || res.status === 403) {
+                    updateStatus("Err: Token", ' #c0392b');
+                } else {
+                    updateStatus("Err: Backend", ' #c0392b');
                 }
-            });
-        }
+            },
+            onerror: function() {
+                inFlight = false;
+                updateStatus("Err: Connect", ' #c0392b');
+            }
+        });
     }
 
     const cyrb53 = function(str, seed = 0) {